@@ -0,0 +1,365 @@
+//! The RLPx framing layer
+//!
+//! All messages that follow the `auth` and `auth-ack` messages are framed.
+//! Frames are encrypted with AES-256-CTR and authenticated with the Keccak-256
+//! based header and frame MACs, using the secrets derived during the handshake.
+//!
+//! The framing is specified at:
+//!
+//! [Framing](https://github.com/ethereum/devp2p/blob/master/rlpx.md#framing)
+//!
+//! A frame has the following form:
+//!
+//! `header-ciphertext || header-mac || frame-ciphertext || frame-mac`
+//!
+//! [`RlpxCodec`] is meant to be used with [`tokio_util::codec::Framed`],
+//! which turns a TCP stream into a [`futures::Stream`] and a [`futures::Sink`]
+//! of frames. It works the same for both the initiator and the recipient,
+//! as the difference is already contained in the [`Secrets`].
+
+use aes::cipher::{BlockEncrypt, KeyInit, KeyIvInit, StreamCipher};
+use aes::Aes256;
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use sha3::{Digest, Keccak256};
+use subtle::ConstantTimeEq;
+use tokio_util::codec::{Decoder, Encoder};
+
+use crate::constants::{
+    FRAME_BLOCK_LEN, FRAME_HEADER_DATA, FRAME_HEADER_LEN, FRAME_MAC_LEN, FRAME_SIZE_MAX,
+};
+use crate::errors::CodecError;
+use crate::secrets::Secrets;
+
+type Aes256Ctr = ctr::Ctr128BE<Aes256>;
+
+/// A codec for encrypted and authenticated RLPx frames
+///
+/// Encodes and decodes `frame-data`, which is `msg-id || msg-data`.
+///
+/// The AES key schedules and the AES-CTR keystream states are zeroized on drop,
+/// and so are the [`Secrets`].
+pub struct RlpxCodec {
+    secrets: Secrets,
+    egress_aes: Aes256Ctr,
+    ingress_aes: Aes256Ctr,
+    mac_aes: Aes256,
+    state: DecodeState,
+}
+
+/// The state of the decoder, which first decodes a header, and then a frame body
+#[derive(Clone, Copy, Debug)]
+enum DecodeState {
+    Header,
+    Body { frame_size: usize },
+}
+
+impl RlpxCodec {
+    /// Creates a new [`RlpxCodec`] from the secrets derived during the handshake
+    ///
+    /// Both the egress and the ingress AES-CTR stream ciphers use `aes-secret`
+    /// as the key, and an all-zero IV.
+    pub fn new(secrets: Secrets) -> Self {
+        let iv = [0u8; FRAME_BLOCK_LEN];
+        let egress_aes = Aes256Ctr::new(secrets.aes_secret().into(), &iv.into());
+        let ingress_aes = Aes256Ctr::new(secrets.aes_secret().into(), &iv.into());
+        let mac_aes = Aes256::new(secrets.mac_secret().into());
+
+        Self {
+            secrets,
+            egress_aes,
+            ingress_aes,
+            mac_aes,
+            state: DecodeState::Header,
+        }
+    }
+}
+
+impl Encoder<Bytes> for RlpxCodec {
+    type Error = CodecError;
+
+    /// Encrypts and authenticates `frame-data`
+    ///
+    /// # Errors
+    /// - [`CodecError::FrameTooLarge`], if the frame size doesn't fit in 3 bytes
+    fn encode(&mut self, frame_data: Bytes, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        let frame_size = frame_data.len();
+        if frame_size > FRAME_SIZE_MAX {
+            return Err(CodecError::FrameTooLarge(frame_size));
+        }
+
+        // header = frame-size || header-data || header-padding
+        let mut header = [0u8; FRAME_HEADER_LEN];
+        header[..3].copy_from_slice(&(frame_size as u32).to_be_bytes()[1..]);
+        header[3..3 + FRAME_HEADER_DATA.len()].copy_from_slice(&FRAME_HEADER_DATA);
+
+        self.egress_aes.apply_keystream(&mut header);
+        let header_mac = update_header_mac(&mut self.secrets.egress_mac, &self.mac_aes, &header);
+
+        let mut frame = BytesMut::with_capacity(padded_len(frame_size));
+        frame.extend_from_slice(&frame_data);
+        frame.resize(padded_len(frame_size), 0);
+
+        self.egress_aes.apply_keystream(&mut frame);
+        let frame_mac = update_frame_mac(&mut self.secrets.egress_mac, &self.mac_aes, &frame);
+
+        dst.reserve(FRAME_HEADER_LEN + FRAME_MAC_LEN + frame.len() + FRAME_MAC_LEN);
+        dst.put_slice(&header);
+        dst.put_slice(&header_mac);
+        dst.put_slice(&frame);
+        dst.put_slice(&frame_mac);
+
+        Ok(())
+    }
+}
+
+impl Decoder for RlpxCodec {
+    type Item = BytesMut;
+    type Error = CodecError;
+
+    /// Authenticates and decrypts a frame
+    ///
+    /// # Returns
+    /// `frame-data`, without the frame padding
+    ///
+    /// # Errors
+    /// - [`CodecError::InvalidHeaderMac`], if the header fails authentication
+    /// - [`CodecError::InvalidFrameMac`], if the frame body fails authentication
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        if let DecodeState::Header = self.state {
+            if src.len() < FRAME_HEADER_LEN + FRAME_MAC_LEN {
+                return Ok(None);
+            }
+
+            let mut header = [0u8; FRAME_HEADER_LEN];
+            header.copy_from_slice(&src[..FRAME_HEADER_LEN]);
+            let header_mac = &src[FRAME_HEADER_LEN..FRAME_HEADER_LEN + FRAME_MAC_LEN];
+
+            let expected_mac =
+                update_header_mac(&mut self.secrets.ingress_mac, &self.mac_aes, &header);
+            if !bool::from(expected_mac.ct_eq(header_mac)) {
+                return Err(CodecError::InvalidHeaderMac);
+            }
+
+            self.ingress_aes.apply_keystream(&mut header);
+            let frame_size = u32::from_be_bytes([0, header[0], header[1], header[2]]) as usize;
+
+            src.advance(FRAME_HEADER_LEN + FRAME_MAC_LEN);
+            self.state = DecodeState::Body { frame_size };
+        }
+
+        let DecodeState::Body { frame_size } = self.state else {
+            unreachable!("The header has just been decoded")
+        };
+
+        let padded_size = padded_len(frame_size);
+        if src.len() < padded_size + FRAME_MAC_LEN {
+            src.reserve(padded_size + FRAME_MAC_LEN - src.len());
+            return Ok(None);
+        }
+
+        let mut frame = src.split_to(padded_size);
+        let frame_mac = src.split_to(FRAME_MAC_LEN);
+
+        let expected_mac = update_frame_mac(&mut self.secrets.ingress_mac, &self.mac_aes, &frame);
+        if !bool::from(expected_mac.ct_eq(&frame_mac)) {
+            return Err(CodecError::InvalidFrameMac);
+        }
+
+        self.ingress_aes.apply_keystream(&mut frame);
+        frame.truncate(frame_size);
+
+        self.state = DecodeState::Header;
+
+        Ok(Some(frame))
+    }
+}
+
+/// Updates the MAC state with a header, and returns the `header-mac`
+///
+/// - `header-mac-seed = aes(mac-secret, keccak256.digest(mac)[:16]) ^ header-ciphertext`
+/// - `mac = keccak256.update(mac, header-mac-seed)`
+/// - `header-mac = keccak256.digest(mac)[:16]`
+fn update_header_mac(
+    mac: &mut Keccak256,
+    mac_aes: &Aes256,
+    header_ciphertext: &[u8; FRAME_HEADER_LEN],
+) -> [u8; FRAME_MAC_LEN] {
+    let mut seed = digest(mac);
+    mac_aes.encrypt_block((&mut seed).into());
+    seed.iter_mut()
+        .zip(header_ciphertext)
+        .for_each(|(s, c)| *s ^= c);
+
+    mac.update(seed);
+
+    digest(mac)
+}
+
+/// Updates the MAC state with a frame body, and returns the `frame-mac`
+///
+/// - `mac = keccak256.update(mac, frame-ciphertext)`
+/// - `frame-mac-seed = aes(mac-secret, keccak256.digest(mac)[:16]) ^ keccak256.digest(mac)[:16]`
+/// - `mac = keccak256.update(mac, frame-mac-seed)`
+/// - `frame-mac = keccak256.digest(mac)[:16]`
+fn update_frame_mac(
+    mac: &mut Keccak256,
+    mac_aes: &Aes256,
+    frame_ciphertext: &[u8],
+) -> [u8; FRAME_MAC_LEN] {
+    mac.update(frame_ciphertext);
+
+    let prev = digest(mac);
+    let mut seed = prev;
+    mac_aes.encrypt_block((&mut seed).into());
+    seed.iter_mut().zip(prev).for_each(|(s, p)| *s ^= p);
+
+    mac.update(seed);
+
+    digest(mac)
+}
+
+/// The first 16 bytes of the current Keccak-256 digest of the MAC state,
+/// without finalizing the state
+fn digest(mac: &Keccak256) -> [u8; FRAME_MAC_LEN] {
+    let mut result = [0u8; FRAME_MAC_LEN];
+    result.copy_from_slice(&mac.clone().finalize()[..FRAME_MAC_LEN]);
+
+    result
+}
+
+/// The length of a frame body, padded to the AES block size
+fn padded_len(frame_size: usize) -> usize {
+    frame_size.div_ceil(FRAME_BLOCK_LEN) * FRAME_BLOCK_LEN
+}
+
+#[cfg(test)]
+mod tests {
+    use ethereum_types::H256;
+    use k256::SecretKey;
+    use rand_core::OsRng;
+
+    use crate::secrets::Role;
+
+    use super::*;
+
+    fn codec_pair() -> (RlpxCodec, RlpxCodec) {
+        let initiator_ephemeral = SecretKey::random(&mut OsRng);
+        let recipient_ephemeral = SecretKey::random(&mut OsRng);
+        let initiator_nonce = H256::random();
+        let recipient_nonce = H256::random();
+
+        let initiator = Secrets::derive(
+            Role::Initiator,
+            &initiator_ephemeral.to_nonzero_scalar(),
+            &recipient_ephemeral.public_key(),
+            &initiator_nonce,
+            &recipient_nonce,
+            b"auth",
+            b"ack",
+        );
+        let recipient = Secrets::derive(
+            Role::Recipient,
+            &recipient_ephemeral.to_nonzero_scalar(),
+            &initiator_ephemeral.public_key(),
+            &initiator_nonce,
+            &recipient_nonce,
+            b"auth",
+            b"ack",
+        );
+
+        (RlpxCodec::new(initiator), RlpxCodec::new(recipient))
+    }
+
+    #[test]
+    fn test_encode_decode_pass() {
+        let (mut initiator, mut recipient) = codec_pair();
+
+        for frame_data in [&b"\x80"[..], &[7u8; 16], &[9u8; 1000]] {
+            let mut buf = BytesMut::new();
+            initiator
+                .encode(Bytes::copy_from_slice(frame_data), &mut buf)
+                .unwrap();
+            assert_eq!(
+                FRAME_HEADER_LEN + 2 * FRAME_MAC_LEN + padded_len(frame_data.len()),
+                buf.len()
+            );
+
+            let decoded = recipient.decode(&mut buf).unwrap().unwrap();
+            assert_eq!(frame_data, &decoded[..]);
+            assert!(buf.is_empty());
+        }
+
+        let mut buf = BytesMut::new();
+        recipient
+            .encode(Bytes::from_static(b"reply"), &mut buf)
+            .unwrap();
+        assert_eq!(b"reply", &initiator.decode(&mut buf).unwrap().unwrap()[..]);
+    }
+
+    #[test]
+    fn test_decode_partial() {
+        let (mut initiator, mut recipient) = codec_pair();
+
+        let mut encoded = BytesMut::new();
+        initiator
+            .encode(Bytes::from_static(b"partial frame"), &mut encoded)
+            .unwrap();
+
+        let mut buf = BytesMut::new();
+        for byte in &encoded[..encoded.len() - 1] {
+            buf.put_u8(*byte);
+            assert!(recipient.decode(&mut buf).unwrap().is_none());
+        }
+        buf.put_u8(encoded[encoded.len() - 1]);
+
+        assert_eq!(
+            b"partial frame",
+            &recipient.decode(&mut buf).unwrap().unwrap()[..]
+        );
+    }
+
+    #[test]
+    fn test_decode_fail_bad_header_mac() {
+        let (mut initiator, mut recipient) = codec_pair();
+
+        let mut buf = BytesMut::new();
+        initiator
+            .encode(Bytes::from_static(b"frame"), &mut buf)
+            .unwrap();
+        buf[FRAME_HEADER_LEN] ^= 0xff;
+
+        assert!(matches!(
+            recipient.decode(&mut buf),
+            Err(CodecError::InvalidHeaderMac)
+        ));
+    }
+
+    #[test]
+    fn test_decode_fail_bad_frame_mac() {
+        let (mut initiator, mut recipient) = codec_pair();
+
+        let mut buf = BytesMut::new();
+        initiator
+            .encode(Bytes::from_static(b"frame"), &mut buf)
+            .unwrap();
+        let len = buf.len();
+        buf[len - FRAME_MAC_LEN - 1] ^= 0xff;
+
+        assert!(matches!(
+            recipient.decode(&mut buf),
+            Err(CodecError::InvalidFrameMac)
+        ));
+    }
+
+    #[test]
+    fn test_encode_fail_too_large() {
+        let (mut initiator, _) = codec_pair();
+
+        let result = initiator.encode(
+            Bytes::from(vec![0u8; FRAME_SIZE_MAX + 1]),
+            &mut BytesMut::new(),
+        );
+
+        assert!(matches!(result, Err(CodecError::FrameTooLarge(_))));
+    }
+}
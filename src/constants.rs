@@ -4,16 +4,90 @@
 
 /* CLI Constants */
 
-pub const TIMEOUT: u64 = 1000;
+pub const CONNECT_TIMEOUT: u64 = 1000;
+pub const HANDSHAKE_TIMEOUT: u64 = 1000;
+pub const READ_TIMEOUT: u64 = 1000;
+pub const MIN_TIMEOUT: u64 = 100;
+pub const CONCURRENCY: usize = 16;
+pub const MAX_INBOUND: usize = 64;
+pub const LISTEN_ADDRESS: &str = "0.0.0.0:30303";
+pub const RETRIES: u32 = 0;
+pub const BACKOFF_BASE: u64 = 500;
+pub const BACKOFF_MAX: u64 = 10_000;
+
+/* Exit Code Constants */
+
+/// All handshakes succeeded, or the failures are within the fail threshold
+pub const EXIT_SUCCESS: u8 = 0;
+/// Some handshakes failed
+pub const EXIT_SOME_FAILED: u8 = 1;
+/// Recipient enodes were provided, but none of them is valid
+pub const EXIT_BAD_INPUT: u8 = 2;
+/// All handshakes failed
+pub const EXIT_ALL_FAILED: u8 = 3;
+/// A runtime error occurred, e.g., binding to the listen address or writing the report failed
+pub const EXIT_RUNTIME_ERROR: u8 = 4;
 
 /* Connection Constants */
 
-// A placeholder for connection constants
+pub const ENODE_SCHEME: &str = "enode://";
+/// The query parameter of an enode that sets the UDP port for node discovery
+pub const ENODE_DISCPORT: &str = "discport";
+pub const HOST_NAME_MAX_LEN: usize = 253;
+pub const HOST_LABEL_MAX_LEN: usize = 63;
 
 /* Handshake Constants */
 
 pub const AUTH_VERSION: usize = 5;
 pub const PUBLIC_KEY_UNCOMPRESSED_LEN: usize = 65;
+pub const SIGNATURE_LEN: usize = 65;
+pub const AES_128_KEY_LEN: usize = 16;
+pub const ECIES_IV_LEN: usize = 16;
+pub const ECIES_MAC_LEN: usize = 32;
+pub const ECIES_OVERHEAD: usize = PUBLIC_KEY_UNCOMPRESSED_LEN + ECIES_IV_LEN + ECIES_MAC_LEN;
+pub const EIP8_SIZE_PREFIX_LEN: usize = 2;
+pub const EIP8_PADDING_MIN: usize = 100;
+pub const EIP8_PADDING_MAX: usize = 300;
+/// The rate, i.e., the block size, of Keccak-256 in bytes
+pub const KECCAK256_RATE: usize = 136;
+
+/* Framing Constants */
+
+pub const FRAME_BLOCK_LEN: usize = 16;
+pub const FRAME_HEADER_LEN: usize = 16;
+pub const FRAME_MAC_LEN: usize = 16;
+pub const FRAME_SIZE_MAX: usize = (1 << 24) - 1;
+/// RLP encoding of `[capability-id, context-id]`, both zero, which is what all implementations send
+pub const FRAME_HEADER_DATA: [u8; 3] = [0xc2, 0x80, 0x80];
+
+/* Identity Constants */
+
+pub const NODE_ID_LEN: usize = 64;
+pub const NODEKEY_HEX_LEN: usize = 64;
+#[cfg(unix)]
+pub const NODEKEY_FILE_MODE: u32 = 0o600;
+pub const KEYSTORE_VERSION: u32 = 3;
+pub const KEYSTORE_CIPHER: &str = "aes-128-ctr";
+pub const KEYSTORE_PRF: &str = "hmac-sha256";
+pub const KEYSTORE_DKLEN: usize = 32;
+pub const KEYSTORE_SALT_LEN: usize = 32;
+/// geth's standard scrypt parameters
+pub const KEYSTORE_SCRYPT_N: u32 = 1 << 18;
+pub const KEYSTORE_SCRYPT_R: u32 = 8;
+pub const KEYSTORE_SCRYPT_P: u32 = 1;
+pub const KEYSTORE_PBKDF2_C: u32 = 262_144;
+
+/* p2p Capability Constants */
+
+pub const P2P_PROTOCOL_VERSION: u64 = 5;
+/// The lowest `p2p` version whose messages after Hello are Snappy-compressed
+pub const SNAPPY_PROTOCOL_VERSION: u64 = 5;
+/// The longest literal that a one-byte Snappy literal tag can describe
+pub const SNAPPY_MAX_SHORT_LITERAL: usize = 60;
+pub const CLIENT_ID: &str = concat!("ethereum-handshake/v", env!("CARGO_PKG_VERSION"));
+pub const CAPABILITIES: [(&str, u64); 2] = [("eth", 67), ("eth", 68)];
+pub const HELLO_MSG_ID: u64 = 0x00;
+pub const DISCONNECT_MSG_ID: u64 = 0x01;
 
 /* Test Constants */
 
@@ -27,3 +101,23 @@ pub const TEST_HOSTNAME: &str = "178.128.136.233:30303";
 pub const TEST_FILE: &str = "tests/test_enodes.txt";
 pub const TEST_FILE_BAD_AND_GOOD: &str = "tests/test_bng.txt";
 pub const TEST_FILE_NON_EXISTENT: &str = "tests/test_nonexistent.txt";
+
+pub const TEST_CONFIG: &str = "tests/test_config.toml";
+
+pub const TEST_HOSTS_FILE: &str = "tests/test_hosts";
+pub const TEST_DNS_HOSTNAME: &str = "node1.lab.internal:30303";
+
+pub const TEST_NODEKEY: &str = "b71c71a67e1177ad4e901695e1b4b9ee17ae16c6668d313eac2f96dbcda3f291";
+/// The node ID of [`TEST_NODEKEY`], which is Static Key B of the EIP-8 test vectors
+pub const TEST_NODEKEY_NODE_ID: &str =
+    "ca634cae0d49acb401d8a4c6b6fe8c55b70d115bf400769cc1400f3258cd3138\
+    7574077f301b421bc84df7266c44e9e6d569fc56be00812904767bf5ccd1fc7f";
+/// The node hash of [`TEST_NODEKEY`], which is the node ID of the example record of EIP-778
+pub const TEST_NODEKEY_NODE_HASH: &str =
+    "a448f24c6d18e575453db13171562b71999873db5b286df957af199ec94617f7";
+pub const TEST_NODEKEY_FILE: &str = "tests/test_nodekey";
+/// The PBKDF2 test vector from the Web3 Secret Storage Definition
+pub const TEST_KEYSTORE_PBKDF2: &str = "tests/test_keystore_pbkdf2.json";
+pub const TEST_KEYSTORE_PASSPHRASE: &str = "testpassword";
+pub const TEST_KEYSTORE_SECRET_KEY: &str =
+    "7a28b5ba57c53603b0b07b56bba752f7784bf506fa95edc395f5cf6c7514fe9d";
@@ -0,0 +1,229 @@
+//! ECIES encryption and decryption, as used by RLPx
+//!
+//! The scheme is specified at:
+//!
+//! [ECIES Encryption](https://github.com/ethereum/devp2p/blob/master/rlpx.md#ecies-encryption)
+//!
+//! - Key derivation: NIST SP 800-56 Concatenation KDF with SHA-256
+//! - Encryption: AES-128-CTR
+//! - Authentication: HMAC-SHA-256 over the IV, the ciphertext and the shared MAC data
+//!
+//! An encrypted message has the following form:
+//!
+//! `R || iv || c || d`
+//!
+//! where `R` is the uncompressed ephemeral public key of the sender,
+//! `iv` is the AES initialization vector, `c` is the ciphertext,
+//! and `d` is the MAC tag.
+
+use aes::cipher::{KeyIvInit, StreamCipher};
+use aes::Aes128;
+use hmac::{Hmac, Mac};
+use k256::ecdh::diffie_hellman;
+use k256::elliptic_curve::sec1::ToEncodedPoint;
+use k256::{NonZeroScalar, PublicKey, SecretKey};
+use rand_core::{OsRng, RngCore};
+use secrecy::Zeroize;
+use sha2::{Digest, Sha256};
+
+use crate::constants::{
+    AES_128_KEY_LEN, ECIES_IV_LEN, ECIES_MAC_LEN, ECIES_OVERHEAD, PUBLIC_KEY_UNCOMPRESSED_LEN,
+};
+use crate::errors::EciesError;
+
+type Aes128Ctr = ctr::Ctr128BE<Aes128>;
+type HmacSha256 = Hmac<Sha256>;
+
+/// Encrypts a message for the owner of the given public key
+///
+/// `shared_mac_data` is authenticated, but it isn't encrypted nor included in the output.
+///
+/// # Returns
+/// The encrypted message, `R || iv || c || d`
+pub fn encrypt(
+    remote_public_key: &PublicKey,
+    plaintext: &[u8],
+    shared_mac_data: &[u8],
+) -> Result<Vec<u8>, EciesError> {
+    let ephemeral_secret = NonZeroScalar::random(&mut OsRng);
+    let ephemeral_public_key = PublicKey::from_secret_scalar(&ephemeral_secret);
+
+    let (mut enc_key, mut mac_key) = derive_keys(&ephemeral_secret, remote_public_key);
+
+    let mut iv = [0u8; ECIES_IV_LEN];
+    OsRng.fill_bytes(&mut iv);
+
+    let mut ciphertext = plaintext.to_vec();
+    Aes128Ctr::new(&enc_key.into(), &iv.into()).apply_keystream(&mut ciphertext);
+
+    let tag = mac(&mac_key, &iv, &ciphertext, shared_mac_data)?.finalize();
+
+    enc_key.zeroize();
+    mac_key.zeroize();
+
+    let mut result = Vec::with_capacity(plaintext.len() + ECIES_OVERHEAD);
+    result.extend_from_slice(ephemeral_public_key.to_encoded_point(false).as_bytes());
+    result.extend_from_slice(&iv);
+    result.extend_from_slice(&ciphertext);
+    result.extend_from_slice(&tag.into_bytes());
+
+    Ok(result)
+}
+
+/// Decrypts a message with our secret key
+///
+/// `shared_mac_data` must be the same as the one the message was encrypted with.
+///
+/// # Returns
+/// The decrypted message
+///
+/// # Errors
+/// - [`EciesError::MessageTooShort`], if the message can't hold all the ECIES parts
+/// - [`EciesError::InvalidPublicKey`], if the sender's ephemeral public key is invalid
+/// - [`EciesError::InvalidMac`], if the message fails authentication
+pub fn decrypt(
+    secret_key: &SecretKey,
+    data: &[u8],
+    shared_mac_data: &[u8],
+) -> Result<Vec<u8>, EciesError> {
+    if data.len() < ECIES_OVERHEAD {
+        return Err(EciesError::MessageTooShort(data.len()));
+    }
+
+    let (ephemeral_public_key, rest) = data.split_at(PUBLIC_KEY_UNCOMPRESSED_LEN);
+    let (iv, rest) = rest.split_at(ECIES_IV_LEN);
+    let (ciphertext, tag) = rest.split_at(rest.len() - ECIES_MAC_LEN);
+
+    let ephemeral_public_key = PublicKey::from_sec1_bytes(ephemeral_public_key)
+        .map_err(|err| EciesError::InvalidPublicKey(err.to_string()))?;
+
+    let (mut enc_key, mut mac_key) =
+        derive_keys(&secret_key.to_nonzero_scalar(), &ephemeral_public_key);
+
+    let result = mac(&mac_key, iv, ciphertext, shared_mac_data)?
+        .verify_slice(tag)
+        .map_err(|_| EciesError::InvalidMac);
+
+    mac_key.zeroize();
+
+    if let Err(err) = result {
+        enc_key.zeroize();
+        return Err(err);
+    }
+
+    let mut plaintext = ciphertext.to_vec();
+    Aes128Ctr::new(&enc_key.into(), iv.into()).apply_keystream(&mut plaintext);
+
+    enc_key.zeroize();
+
+    Ok(plaintext)
+}
+
+/// Derives the encryption key and the MAC key from a shared secret
+///
+/// - `kE || kM = KDF(S, 32)`, where `S` is the ECDH shared secret
+/// - the encryption key is `kE`
+/// - the MAC key is `sha256(kM)`
+fn derive_keys(
+    secret: &NonZeroScalar,
+    public_key: &PublicKey,
+) -> ([u8; AES_128_KEY_LEN], [u8; ECIES_MAC_LEN]) {
+    let shared_secret = diffie_hellman(secret, public_key.as_affine());
+
+    let mut key = kdf(shared_secret.raw_secret_bytes());
+
+    let mut enc_key = [0u8; AES_128_KEY_LEN];
+    enc_key.copy_from_slice(&key[..AES_128_KEY_LEN]);
+    let mac_key: [u8; ECIES_MAC_LEN] = Sha256::digest(&key[AES_128_KEY_LEN..]).into();
+
+    key.zeroize();
+
+    (enc_key, mac_key)
+}
+
+/// NIST SP 800-56 Concatenation Key Derivation Function, with SHA-256
+///
+/// Derives a 32-byte key, which only takes one hash round, with the counter set to 1.
+/// The `OtherInfo` parameter is empty, as in RLPx.
+fn kdf(shared_secret: &[u8]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(1u32.to_be_bytes());
+    hasher.update(shared_secret);
+
+    hasher.finalize().into()
+}
+
+/// Creates an HMAC-SHA-256 instance over `iv || ciphertext || shared_mac_data`
+fn mac(
+    mac_key: &[u8],
+    iv: &[u8],
+    ciphertext: &[u8],
+    shared_mac_data: &[u8],
+) -> Result<HmacSha256, EciesError> {
+    let mut mac =
+        HmacSha256::new_from_slice(mac_key).map_err(|err| EciesError::MacKey(err.to_string()))?;
+    mac.update(iv);
+    mac.update(ciphertext);
+    mac.update(shared_mac_data);
+
+    Ok(mac)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MESSAGE: &[u8] = b"The quick brown fox jumps over the lazy dog";
+    const SHARED_MAC_DATA: &[u8] = &[0x01, 0x23];
+
+    #[test]
+    fn test_encrypt_decrypt_pass() {
+        let secret_key = SecretKey::random(&mut OsRng);
+
+        let encrypted = encrypt(&secret_key.public_key(), MESSAGE, SHARED_MAC_DATA).unwrap();
+        assert_eq!(MESSAGE.len() + ECIES_OVERHEAD, encrypted.len());
+
+        let decrypted = decrypt(&secret_key, &encrypted, SHARED_MAC_DATA).unwrap();
+        assert_eq!(MESSAGE, decrypted);
+    }
+
+    #[test]
+    fn test_decrypt_fail_wrong_shared_mac_data() {
+        let secret_key = SecretKey::random(&mut OsRng);
+
+        let encrypted = encrypt(&secret_key.public_key(), MESSAGE, SHARED_MAC_DATA).unwrap();
+
+        let result = decrypt(&secret_key, &encrypted, &[0x01, 0x24]);
+        assert_eq!(Err(EciesError::InvalidMac), result);
+    }
+
+    #[test]
+    fn test_decrypt_fail_wrong_key() {
+        let secret_key = SecretKey::random(&mut OsRng);
+        let other_secret_key = SecretKey::random(&mut OsRng);
+
+        let encrypted = encrypt(&secret_key.public_key(), MESSAGE, SHARED_MAC_DATA).unwrap();
+
+        let result = decrypt(&other_secret_key, &encrypted, SHARED_MAC_DATA);
+        assert_eq!(Err(EciesError::InvalidMac), result);
+    }
+
+    #[test]
+    fn test_decrypt_fail_tampered_ciphertext() {
+        let secret_key = SecretKey::random(&mut OsRng);
+
+        let mut encrypted = encrypt(&secret_key.public_key(), MESSAGE, SHARED_MAC_DATA).unwrap();
+        encrypted[PUBLIC_KEY_UNCOMPRESSED_LEN + ECIES_IV_LEN] ^= 0xff;
+
+        let result = decrypt(&secret_key, &encrypted, SHARED_MAC_DATA);
+        assert_eq!(Err(EciesError::InvalidMac), result);
+    }
+
+    #[test]
+    fn test_decrypt_fail_too_short() {
+        let secret_key = SecretKey::random(&mut OsRng);
+
+        let result = decrypt(&secret_key, &[0u8; ECIES_OVERHEAD - 1], SHARED_MAC_DATA);
+        assert_eq!(Err(EciesError::MessageTooShort(ECIES_OVERHEAD - 1)), result);
+    }
+}
@@ -1,15 +1,100 @@
 //! The errors that are used in the library and that can be used in binary crates.
 
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
 use thiserror::Error;
 
-/// Input errors that occur during dialing
+use crate::messages::DisconnectReason;
+
+/// Errors that occur during listening for connections
+#[derive(Debug, Error)]
+pub enum ListenError {
+    #[error("Failed to bind to {0}: {1}")]
+    BindError(SocketAddr, String),
+
+    #[error("I/O Error: {0}")]
+    IOError(#[from] std::io::Error),
+}
+
+/// Errors that occur during writing of machine-readable output
+#[derive(Debug, Error)]
+pub enum OutputError {
+    #[error("JSON error: {0}")]
+    JsonError(#[from] serde_json::Error),
+
+    #[error("I/O Error: {0}")]
+    IOError(#[from] std::io::Error),
+}
+
+/// Errors that occur during loading or saving of our node's identity
+#[derive(Debug, Error)]
+pub enum IdentityError {
+    #[error("Invalid node key: {0}")]
+    InvalidNodeKey(String),
+
+    #[error("Failed to read node key from {0}: {1}")]
+    ReadError(PathBuf, String),
+
+    #[error("Failed to write node key to {0}: {1}")]
+    WriteError(PathBuf, String),
+
+    #[error("Invalid keystore: {0}")]
+    InvalidKeystore(String),
+
+    #[error("Wrong passphrase, or corrupted keystore")]
+    WrongPassphrase,
+
+    #[error("Failed to read passphrase: {0}")]
+    PassphraseError(String),
+
+    #[error("A node key file or a keystore is needed to save a new node key")]
+    NoKeyFile,
+}
+
+/// Errors that occur during parsing of a node ID
 #[derive(Debug, Error, PartialEq)]
-pub enum DialError {
-    #[error("Enode parse error: {0}")]
-    EnodeParseError(#[from] EnodeParseError),
+pub enum NodeIdError {
+    #[error("Hex decode error: {0}")]
+    HexDecodeError(String),
+
+    #[error("Expected 128 hex characters, got {0}")]
+    InvalidHexLength(usize),
+
+    #[error("Invalid node ID length: {0} bytes")]
+    InvalidLength(usize),
 
-    #[error("Connection error: {0}")]
-    ConnectionError(#[from] ConnError),
+    #[error("Invalid public key: {0}")]
+    InvalidPublicKey(String),
+}
+
+/// Errors that occur during loading of a configuration file
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("Failed to read configuration file {0}: {1}")]
+    ReadError(PathBuf, String),
+
+    #[error("Invalid configuration file {0}: {1}")]
+    ParseError(PathBuf, String),
+
+    #[error("Invalid `{0}` in configuration file: {1}")]
+    InvalidValue(&'static str, String),
+}
+
+/// Errors that occur during resolution of a recipient's host name
+#[derive(Debug, Error, PartialEq)]
+pub enum ResolveError {
+    #[error("Failed to read hosts file {0}: {1}")]
+    ReadError(PathBuf, String),
+
+    #[error("Invalid hosts file {0}, line {1}: {2}")]
+    ParseError(PathBuf, usize, String),
+
+    #[error("Failed to resolve {0}: {1}")]
+    LookupError(String, String),
+
+    #[error("No addresses found for {0}")]
+    NotFound(String),
 }
 
 /// Errors during parsing of an enode
@@ -18,34 +103,98 @@ pub enum EnodeParseError {
     #[error("Invalid recipient's enode. See help for the correct format.")]
     InvalidRecipientEnode,
 
-    #[error("Invalid recipient's public key: {0}")]
-    InvalidRecipientUserName(String),
+    #[error("Invalid scheme of recipient's enode, expected enode://: {0}")]
+    InvalidScheme(String),
+
+    #[error("Invalid recipient's node ID: {0}")]
+    InvalidRecipientNodeId(#[from] NodeIdError),
 
     #[error("Invalid recipient's host name: {0}")]
     InvalidRecipientHostName(String),
-}
 
-/// Connection errors
-#[derive(Debug, Error, PartialEq)]
-pub enum ConnError {
-    #[error("TCP stream error: {0}")]
-    TcpStreamError(String),
+    #[error("Invalid recipient's port, expected 1 to 65535: {0}")]
+    InvalidRecipientPort(String),
+
+    #[error("Invalid query parameter of recipient's enode, expected discport=<port>: {0}")]
+    InvalidQueryParameter(String),
 
-    #[error("Timeout error: {0}")]
-    TimeoutError(#[from] tokio::time::error::Elapsed),
+    #[error("Invalid recipient's discovery port, expected 1 to 65535: {0}")]
+    InvalidDiscoveryPort(String),
 }
 
 /// Errors during the handshake procedure
 #[derive(Debug, Error)]
 pub enum HandshakeError {
-    #[error("Hex decode error: {0}")]
-    HexDecodeError(String),
-
     #[error("Sec1 error: {0}")]
     Sec1Error(String),
 
-    #[error("ECIES encrypt error: {0}")]
-    EciesEncryptError(String),
+    #[error("Signature error: {0}")]
+    SignatureError(String),
+
+    #[error("ECIES error: {0}")]
+    EciesError(#[from] EciesError),
+
+    #[error("Codec error: {0}")]
+    CodecError(#[from] CodecError),
+
+    #[error("RLP decode error: {0}")]
+    RlpDecodeError(#[from] rlp::DecoderError),
+
+    #[error("Unexpected message with ID {0:#04x}")]
+    UnexpectedMessage(u64),
+
+    #[error("Disconnected: {0}")]
+    Disconnected(DisconnectReason),
+
+    #[error("Connection closed by peer")]
+    ConnectionClosed,
+
+    #[error("Unexpected identity: the peer's Hello has the node ID {0}")]
+    UnexpectedIdentity(String),
+
+    #[error(
+        "Auth rejected: the recipient closed the connection without sending any auth-ack; \
+        presumably, our auth was encrypted to a wrong node key"
+    )]
+    AuthRejected,
+
+    #[error("Timed out waiting for the peer to send data")]
+    ReadTimeout,
+
+    #[error("Message too large: {0} bytes")]
+    MessageTooLarge(usize),
+
+    #[error("I/O Error: {0}")]
+    IOError(#[from] std::io::Error),
+}
+
+/// Errors during ECIES encryption and decryption
+#[derive(Debug, Error, PartialEq)]
+pub enum EciesError {
+    #[error("Invalid public key: {0}")]
+    InvalidPublicKey(String),
+
+    #[error("Message too short: {0} bytes")]
+    MessageTooShort(usize),
+
+    #[error("Invalid MAC key: {0}")]
+    MacKey(String),
+
+    #[error("Invalid MAC")]
+    InvalidMac,
+}
+
+/// Errors on the RLPx framing layer
+#[derive(Debug, Error)]
+pub enum CodecError {
+    #[error("Invalid header MAC")]
+    InvalidHeaderMac,
+
+    #[error("Invalid frame MAC")]
+    InvalidFrameMac,
+
+    #[error("Frame too large: {0} bytes")]
+    FrameTooLarge(usize),
 
     #[error("I/O Error: {0}")]
     IOError(#[from] std::io::Error),
@@ -1,70 +1,130 @@
 //! Functions for handling the handshake procedure
 //!
 //! - Initiate handshake
-//! - Respond to a handshake request - not implemented
+//! - Respond to a handshake request
+//!
+//! Only the EIP-8 format of the `auth` and `auth-ack` messages is supported.
+
+use std::io;
+use std::time::Duration;
 
-// use ecies::{decrypt, utils::generate_keypair};
 use ethereum_types::H256;
-use hex;
-use k256::{ecdh::EphemeralSecret, EncodedPoint, PublicKey, SecretKey};
-// use k256::ecdsa::{self, Signature, signature::Signer, SigningKey};
-// use k256::ecdsa::signature::SignerMut;
-use rand_core::OsRng;
-// use rlp::{Rlp, RlpStream};
-use rlp::RlpStream;
+use futures::{SinkExt, StreamExt};
+use k256::ecdh::diffie_hellman;
+use k256::ecdsa::{RecoveryId, Signature, SigningKey, VerifyingKey};
+use k256::{NonZeroScalar, PublicKey, SecretKey};
+use rand_core::{OsRng, RngCore};
+use rlp::{DecoderError, PayloadInfo, Rlp, RlpStream};
 use secrecy::{ExposeSecret, Secret, Zeroize};
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::TcpStream;
+use tokio_util::codec::Framed;
 use tracing::{debug, info, instrument};
 
-use crate::constants::{AUTH_VERSION, PUBLIC_KEY_UNCOMPRESSED_LEN};
+use crate::codec::RlpxCodec;
+use crate::constants::{
+    AUTH_VERSION, ECIES_OVERHEAD, EIP8_PADDING_MAX, EIP8_PADDING_MIN, EIP8_SIZE_PREFIX_LEN,
+    SIGNATURE_LEN, SNAPPY_PROTOCOL_VERSION,
+};
+use crate::ecies;
 use crate::errors::HandshakeError;
+use crate::identity::NodeId;
+use crate::messages::{DisconnectReason, Hello, P2pMessage};
+use crate::secrets::{Role, Secrets};
 
 /// The handshake procedure between our client as initiator and a recipient node
 ///
 /// The procedure is defined at:
 /// https://github.com/ethereum/devp2p/blob/master/rlpx.md
+///
+/// Every read from the recipient must complete within `read_timeout`.
 #[instrument(level = "trace", skip_all)]
 pub async fn initiate_handshake(
     static_secret_key: &SecretKey,
     stream: &mut TcpStream,
-    username: String,
+    listen_port: u16,
+    read_timeout: Duration,
+    recipient_public_key: &PublicKey,
     hostname: String,
-) -> Result<(), HandshakeError> {
+) -> Result<Hello, HandshakeError> {
     info!("Starting handshake with {}...", hostname);
 
     // 1. initiator connects to recipient and sends its auth message
-    step_1(static_secret_key, stream, &username, &hostname).await?;
+    let sent_auth = step_1(static_secret_key, stream, recipient_public_key, &hostname).await?;
 
     // 5. initiator receives auth-ack and derives secrets
-    step_5(stream, &username, &hostname).await?;
+    let secrets = step_5(
+        static_secret_key,
+        stream,
+        &hostname,
+        read_timeout,
+        sent_auth,
+    )
+    .await?;
+
+    let mut framed = Framed::new(stream, RlpxCodec::new(secrets));
 
     // 6. initiator sends its first encrypted frame containing initiator Hello message
-    // TODO
+    step_6(static_secret_key, &mut framed, &hostname, listen_port).await?;
 
     // 8. initiator receives and authenticates first encrypted frame
-    // TODO
+    let hello = step_8(&mut framed, &hostname, read_timeout, recipient_public_key).await?;
 
     // 9. cryptographic handshake is complete if MAC of first encrypted frame is valid on both sides
-    // TODO
+    info!("Handshake with {} complete: {}", hostname, hello);
 
-    Ok(())
+    Ok(hello)
 }
 
 /// The handshake procedure between our client as recipient and an initiator node
+///
+/// The procedure is defined at:
+/// https://github.com/ethereum/devp2p/blob/master/rlpx.md
+///
+/// # Returns
+/// A tuple of:
+/// - the initiator's static public key, which is authenticated by the handshake
+/// - the initiator's Hello message
+///
+/// Every read from the initiator must complete within `read_timeout`.
 #[instrument(level = "trace", skip_all)]
-pub async fn respond_to_handshake() {
-    unimplemented!()
+pub async fn respond_to_handshake(
+    static_secret_key: &SecretKey,
+    stream: &mut TcpStream,
+    listen_port: u16,
+    read_timeout: Duration,
+) -> Result<(PublicKey, Hello), HandshakeError> {
+    let hostname = stream
+        .peer_addr()
+        .map_or_else(|_| "unknown".to_string(), |addr| addr.to_string());
+
+    info!("Responding to handshake from {}...", hostname);
 
     // 2. recipient accepts, decrypts and verifies auth (checks that recovery of signature == keccak256(ephemeral-pubk))
+    let received_auth = step_2(static_secret_key, stream, &hostname, read_timeout).await?;
+    let initiator_public_key = received_auth.initiator_public_key;
 
     // 3. recipient generates auth-ack message from remote-ephemeral-pubk and nonce
+    let sent_ack = step_3(stream, &hostname, &received_auth).await?;
 
     // 4. recipient derives secrets and sends the first encrypted frame containing the Hello message
+    let mut framed = step_4(
+        static_secret_key,
+        stream,
+        &hostname,
+        listen_port,
+        received_auth,
+        sent_ack,
+    )
+    .await?;
 
     // 7. recipient receives and authenticates first encrypted frame
+    let hello = step_7(&mut framed, &hostname, read_timeout, &initiator_public_key).await?;
 
     // 9. cryptographic handshake is complete if MAC of first encrypted frame is valid on both sides
+    info!("Handshake with {} complete: {}", hostname, hello);
+
+    Ok((initiator_public_key, hello))
 }
 
 /// Step 1: initiator connects to recipient and sends its `auth` message
@@ -72,68 +132,37 @@ pub async fn respond_to_handshake() {
 async fn step_1(
     static_secret_key: &SecretKey,
     stream: &mut TcpStream,
-    username: &String,
+    recipient_public_key: &PublicKey,
     hostname: &String,
-) -> Result<(), HandshakeError> {
+) -> Result<SentAuth, HandshakeError> {
     debug!("Begin Step 1 with {}", hostname);
 
-    let _static_public_key = EncodedPoint::from(static_secret_key.public_key());
-    let initiator_public_key = static_secret_key.public_key();
-    let initiator_public_key = &(*(initiator_public_key.to_sec1_bytes()))[1..];
+    let initiator_public_key = NodeId::from_secret_key(static_secret_key);
 
-    let username = match hex::decode(username) {
-        Ok(name) => name,
-        Err(err) => Err(HandshakeError::HexDecodeError(err.to_string()))?,
-    };
-    let mut aux = [0; PUBLIC_KEY_UNCOMPRESSED_LEN];
-    aux[0] = 4;
-    aux[1..].copy_from_slice(username.as_ref());
-    let recipient_public_key = match PublicKey::from_sec1_bytes(aux.as_ref()) {
-        Ok(key) => key,
-        Err(err) => Err(HandshakeError::Sec1Error(err.to_string()))?,
-    };
+    let ephemeral_secret = Secret::new(NonZeroScalar::random(&mut OsRng));
 
-    let init_ephemeral_secret = EphemeralSecret::random(&mut OsRng);
-    let init_ephemeral_secret = Secret::new(init_ephemeral_secret);
-    let _init_ephemeral_pubkey_bytes =
-        EncodedPoint::from(init_ephemeral_secret.expose_secret().public_key());
-
-    let init_shared = init_ephemeral_secret
-        .expose_secret()
-        .diffie_hellman(&recipient_public_key);
-    let init_shared = H256::from_slice(&init_shared.raw_secret_bytes()[..32]);
+    // static-shared-secret = ecdh.agree(privkey, remote-pubk)
+    let static_shared_secret = diffie_hellman(
+        static_secret_key.to_nonzero_scalar(),
+        recipient_public_key.as_affine(),
+    );
+    let static_shared_secret = H256::from_slice(static_shared_secret.raw_secret_bytes());
 
     let initiator_nonce = H256::random();
 
-    let _init_ephemeral_pubkey = init_ephemeral_secret.expose_secret().public_key();
-
-    // TODO: compute signature
-    let message = init_shared ^ initiator_nonce;
-    let signature = message;
-    // let signature: Signature = init_ephemeral_secret
-    //     .expose_secret()
-    //     .sign(message.as_fixed_bytes());
+    // sig = ecdsa.sign(ephemeral-privk, static-shared-secret ^ nonce)
+    let message = static_shared_secret ^ initiator_nonce;
+    let signature = sign(ephemeral_secret.expose_secret(), &message)?;
 
     // "auth_body" is an RLP stream of 4 values
     let mut rlp_stream = RlpStream::new_list(4);
-    rlp_stream.append(&signature);
-    // rlp_stream.append(&_init_ephemeral_pubkey.to_sec1_bytes().deref());
-    rlp_stream.append(&initiator_public_key);
+    rlp_stream.append(&signature.as_ref());
+    rlp_stream.append(&initiator_public_key.as_bytes().as_ref());
     rlp_stream.append(&initiator_nonce);
     rlp_stream.append(&AUTH_VERSION);
     let auth_body = rlp_stream.out();
 
-    let _enc_auth_body = ecies::encrypt(username.as_ref(), auth_body.as_ref());
-    let _enc_auth_body = ecies::encrypt(aux.as_ref(), auth_body.as_ref());
-
-    // TODO: add auth-padding, auth-size?
-    let enc_auth_body = ecies::encrypt(&recipient_public_key.to_sec1_bytes(), auth_body.as_ref())
-        .map_err(|err| HandshakeError::EciesEncryptError(err.to_string()))?;
-
-    let _auth_size = enc_auth_body.len();
-
-    // TODO: prepend with auth_size
-    let auth = enc_auth_body;
+    let auth = seal_eip8_message(recipient_public_key, &auth_body)?;
 
     // send the "auth" message to recipient
     stream.write_all(auth.as_ref()).await?;
@@ -144,31 +173,918 @@ async fn step_1(
         hostname
     );
 
-    Ok(())
+    Ok(SentAuth {
+        ephemeral_secret,
+        initiator_nonce,
+        auth,
+    })
+}
+
+/// What the initiator keeps from Step 1 for deriving secrets in Step 5
+struct SentAuth {
+    ephemeral_secret: Secret<NonZeroScalar>,
+    initiator_nonce: H256,
+    auth: Vec<u8>,
+}
+
+/// Step 2: recipient accepts, decrypts and verifies `auth`
+///
+/// Recovers the initiator's ephemeral public key from the signature,
+/// which is made over `static-shared-secret ^ initiator-nonce`.
+#[instrument(level = "trace", skip_all)]
+async fn step_2(
+    static_secret_key: &SecretKey,
+    stream: &mut TcpStream,
+    hostname: &String,
+    read_timeout: Duration,
+) -> Result<ReceivedAuth, HandshakeError> {
+    debug!("Begin Step 2 with {}", hostname);
+
+    // receive the "auth" message from initiator
+    let auth = read_eip8_message(stream, read_timeout).await?;
+
+    let mut auth_body = open_eip8_message(static_secret_key, &auth)?;
+
+    let decoded = decode_auth_body(&auth_body);
+
+    auth_body.zeroize();
+
+    let (signature, initiator_public_key, initiator_nonce, auth_version) = decoded?;
+
+    // static-shared-secret = ecdh.agree(privkey, remote-pubk)
+    let static_shared_secret = diffie_hellman(
+        static_secret_key.to_nonzero_scalar(),
+        initiator_public_key.as_affine(),
+    );
+    let static_shared_secret = H256::from_slice(static_shared_secret.raw_secret_bytes());
+
+    // the signature must be made over static-shared-secret ^ nonce with initiator's ephemeral key
+    let message = static_shared_secret ^ initiator_nonce;
+    let initiator_ephemeral_public_key = recover(&signature, &message)?;
+
+    debug!(
+        "Received and verified the auth message, version {}, from initiator {}. End of Step 2.",
+        auth_version, hostname
+    );
+
+    Ok(ReceivedAuth {
+        initiator_public_key,
+        initiator_ephemeral_public_key,
+        initiator_nonce,
+        auth,
+    })
+}
+
+/// What the recipient keeps from Step 2 for the following steps
+struct ReceivedAuth {
+    initiator_public_key: PublicKey,
+    initiator_ephemeral_public_key: PublicKey,
+    initiator_nonce: H256,
+    auth: Vec<u8>,
+}
+
+/// Decodes a decrypted `auth` message body
+///
+/// `auth-body = [sig, initiator-pubk, initiator-nonce, auth-vsn, ...]`
+///
+/// As required by EIP-8, additional list elements and trailing padding are ignored.
+///
+/// # Returns
+/// A tuple of the signature, the initiator's static public key, its nonce,
+/// and the auth version
+///
+/// # Errors
+/// - [`HandshakeError::RlpDecodeError`], if the body isn't a valid RLP list
+/// - [`HandshakeError::Sec1Error`], if the initiator's public key is invalid
+fn decode_auth_body(
+    auth_body: &[u8],
+) -> Result<([u8; SIGNATURE_LEN], PublicKey, H256, u64), HandshakeError> {
+    let rlp = Rlp::new(strip_padding(auth_body)?);
+
+    let signature: [u8; SIGNATURE_LEN] = rlp
+        .at(0)?
+        .data()?
+        .try_into()
+        .map_err(|_| HandshakeError::SignatureError("Invalid signature length".to_string()))?;
+    let initiator_public_key = public_key_from_bytes(rlp.at(1)?.data()?)?;
+    let initiator_nonce: H256 = rlp.val_at(2)?;
+    let auth_version: u64 = rlp.val_at(3)?;
+
+    Ok((
+        signature,
+        initiator_public_key,
+        initiator_nonce,
+        auth_version,
+    ))
+}
+
+/// Step 3: recipient generates `auth-ack` message from remote-ephemeral-pubk and nonce
+#[instrument(level = "trace", skip_all)]
+async fn step_3(
+    stream: &mut TcpStream,
+    hostname: &String,
+    received_auth: &ReceivedAuth,
+) -> Result<SentAck, HandshakeError> {
+    debug!("Begin Step 3 with {}", hostname);
+
+    let ephemeral_secret = Secret::new(NonZeroScalar::random(&mut OsRng));
+    let ephemeral_public_key = PublicKey::from_secret_scalar(ephemeral_secret.expose_secret());
+
+    let recipient_nonce = H256::random();
+
+    // "ack_body" is an RLP stream of 3 values
+    let mut rlp_stream = RlpStream::new_list(3);
+    rlp_stream.append(&NodeId::from(&ephemeral_public_key).as_bytes().as_ref());
+    rlp_stream.append(&recipient_nonce);
+    rlp_stream.append(&AUTH_VERSION);
+    let ack_body = rlp_stream.out();
+
+    let ack = seal_eip8_message(&received_auth.initiator_public_key, &ack_body)?;
+
+    // send the "auth-ack" message to initiator
+    stream.write_all(ack.as_ref()).await?;
+    stream.flush().await?;
+
+    debug!(
+        "Sent the auth-ack message to initiator {}. End of Step 3.",
+        hostname
+    );
+
+    Ok(SentAck {
+        ephemeral_secret,
+        recipient_nonce,
+        ack,
+    })
+}
+
+/// What the recipient keeps from Step 3 for deriving secrets in Step 4
+struct SentAck {
+    ephemeral_secret: Secret<NonZeroScalar>,
+    recipient_nonce: H256,
+    ack: Vec<u8>,
+}
+
+/// Step 4: recipient derives secrets and sends the first encrypted frame containing the Hello message
+#[instrument(level = "trace", skip_all)]
+async fn step_4<'a>(
+    static_secret_key: &SecretKey,
+    stream: &'a mut TcpStream,
+    hostname: &String,
+    listen_port: u16,
+    received_auth: ReceivedAuth,
+    sent_ack: SentAck,
+) -> Result<Framed<&'a mut TcpStream, RlpxCodec>, HandshakeError> {
+    debug!("Begin Step 4 with {}", hostname);
+
+    // derive secrets
+    let secrets = Secrets::derive(
+        Role::Recipient,
+        sent_ack.ephemeral_secret.expose_secret(),
+        &received_auth.initiator_ephemeral_public_key,
+        &received_auth.initiator_nonce,
+        &sent_ack.recipient_nonce,
+        &received_auth.auth,
+        &sent_ack.ack,
+    );
+
+    let mut framed = Framed::new(stream, RlpxCodec::new(secrets));
+
+    send_hello(static_secret_key, listen_port, &mut framed).await?;
+
+    debug!(
+        "Derived secrets and sent Hello to initiator {}. End of Step 4.",
+        hostname
+    );
+
+    Ok(framed)
+}
+
+/// Appends random EIP-8 padding to a message body
+///
+/// The padding is between [`EIP8_PADDING_MIN`] and [`EIP8_PADDING_MAX`] bytes long,
+/// so that EIP-8 messages can be told apart from the legacy (pre-EIP-8) format.
+fn append_padding(body: &mut Vec<u8>) {
+    let padding_len =
+        EIP8_PADDING_MIN + (OsRng.next_u32() as usize) % (EIP8_PADDING_MAX - EIP8_PADDING_MIN + 1);
+
+    let mut padding = vec![0u8; padding_len];
+    OsRng.fill_bytes(&mut padding);
+
+    body.extend_from_slice(&padding);
+}
+
+/// Signs a 32-byte message with the given secret key
+///
+/// Produces a recoverable secp256k1 ECDSA signature, as required by RLPx.
+///
+/// # Returns
+/// A 65-byte signature in the form of `r || s || v`, where `v` is the recovery id
+fn sign(secret_key: &NonZeroScalar, message: &H256) -> Result<[u8; SIGNATURE_LEN], HandshakeError> {
+    let signing_key = SigningKey::from(*secret_key);
+    let (signature, recovery_id) = signing_key
+        .sign_prehash_recoverable(message.as_bytes())
+        .map_err(|err| HandshakeError::SignatureError(err.to_string()))?;
+
+    let mut result = [0u8; SIGNATURE_LEN];
+    result[..SIGNATURE_LEN - 1].copy_from_slice(&signature.to_bytes());
+    result[SIGNATURE_LEN - 1] = recovery_id.to_byte();
+
+    Ok(result)
+}
+
+/// Recovers the public key that a 32-byte message was signed with
+///
+/// The signature must be in the form of `r || s || v`, where `v` is the recovery id.
+///
+/// # Errors
+/// - [`HandshakeError::SignatureError`], if the signature is invalid
+fn recover(signature: &[u8; SIGNATURE_LEN], message: &H256) -> Result<PublicKey, HandshakeError> {
+    let recovery_id = RecoveryId::from_byte(signature[SIGNATURE_LEN - 1])
+        .ok_or_else(|| HandshakeError::SignatureError("Invalid recovery id".to_string()))?;
+    let sig = Signature::from_slice(&signature[..SIGNATURE_LEN - 1])
+        .map_err(|err| HandshakeError::SignatureError(err.to_string()))?;
+
+    let verifying_key = VerifyingKey::recover_from_prehash(message.as_bytes(), &sig, recovery_id)
+        .map_err(|err| HandshakeError::SignatureError(err.to_string()))?;
+
+    Ok(PublicKey::from(verifying_key))
 }
 
 /// Step 5: initiator receives `auth-ack` and derives secrets
 #[instrument(level = "trace", skip_all)]
 async fn step_5(
+    static_secret_key: &SecretKey,
     stream: &mut TcpStream,
-    _username: &str,
     hostname: &String,
-) -> Result<(), HandshakeError> {
+    read_timeout: Duration,
+    sent_auth: SentAuth,
+) -> Result<Secrets, HandshakeError> {
     debug!("Begin Step 5 with {}", hostname);
 
-    // receive the "auth-ack" message from recipient
+    // a recipient that can't decrypt our auth message, because it was encrypted
+    // to another public key, closes the connection without sending anything
+    let mut first_byte = [0u8; 1];
+    let peeked = tokio::time::timeout(read_timeout, stream.peek(&mut first_byte))
+        .await
+        .map_err(|_| HandshakeError::ReadTimeout)??;
+    if peeked == 0 {
+        return Err(HandshakeError::AuthRejected);
+    }
+
+    // receive the "auth-ack" message from recipient; a connection that closes
+    // in the middle of it is an ordinary connection failure
+    let auth_ack = read_eip8_message(stream, read_timeout)
+        .await
+        .map_err(|err| match err {
+            HandshakeError::IOError(err) if err.kind() == io::ErrorKind::UnexpectedEof => {
+                HandshakeError::ConnectionClosed
+            }
+            err => err,
+        })?;
+
+    let mut ack_body = open_eip8_message(static_secret_key, &auth_ack)?;
+
+    let ack = decode_auth_ack(&ack_body);
 
-    // pin the buffer for security reasons so that it can't be relocated and copied over,
-    // so that we're certain we've manually zeroized the only instance at the end
-    let mut auth_ack = Box::pin([0u8; 1024]);
-    let auth_ack_len = Secret::new(stream.read(&mut *auth_ack).await?);
-    eprintln!("{:?}", *auth_ack);
-    dbg!(auth_ack_len.expose_secret());
+    ack_body.zeroize();
+
+    let ack = ack?;
+
+    debug!(
+        "Received the auth-ack message, version {}, from recipient {}.",
+        ack.ack_version, hostname
+    );
 
     // derive secrets
+    let secrets = Secrets::derive(
+        Role::Initiator,
+        sent_auth.ephemeral_secret.expose_secret(),
+        &ack.recipient_ephemeral_public_key,
+        &sent_auth.initiator_nonce,
+        &ack.recipient_nonce,
+        &sent_auth.auth,
+        &auth_ack,
+    );
+
+    debug!("Derived secrets. End of Step 5 with {}.", hostname);
 
-    // TODO: zeroize secrets
-    auth_ack.zeroize();
+    Ok(secrets)
+}
+
+/// Step 6: initiator sends its first encrypted frame containing initiator Hello message
+#[instrument(level = "trace", skip_all)]
+async fn step_6(
+    static_secret_key: &SecretKey,
+    framed: &mut Framed<&mut TcpStream, RlpxCodec>,
+    hostname: &String,
+    listen_port: u16,
+) -> Result<(), HandshakeError> {
+    debug!("Begin Step 6 with {}", hostname);
+
+    send_hello(static_secret_key, listen_port, framed).await?;
+
+    debug!("Sent Hello to recipient {}. End of Step 6.", hostname);
 
     Ok(())
 }
+
+/// Step 7: recipient receives and authenticates first encrypted frame
+#[instrument(level = "trace", skip_all)]
+async fn step_7(
+    framed: &mut Framed<&mut TcpStream, RlpxCodec>,
+    hostname: &String,
+    read_timeout: Duration,
+    initiator_public_key: &PublicKey,
+) -> Result<Hello, HandshakeError> {
+    debug!("Begin Step 7 with {}", hostname);
+
+    let hello = receive_hello(framed, read_timeout, initiator_public_key).await?;
+
+    debug!("Received Hello from initiator {}. End of Step 7.", hostname);
+
+    Ok(hello)
+}
+
+/// Step 8: initiator receives and authenticates first encrypted frame
+#[instrument(level = "trace", skip_all)]
+async fn step_8(
+    framed: &mut Framed<&mut TcpStream, RlpxCodec>,
+    hostname: &String,
+    read_timeout: Duration,
+    recipient_public_key: &PublicKey,
+) -> Result<Hello, HandshakeError> {
+    debug!("Begin Step 8 with {}", hostname);
+
+    let hello = receive_hello(framed, read_timeout, recipient_public_key).await?;
+
+    debug!("Received Hello from recipient {}. End of Step 8.", hostname);
+
+    Ok(hello)
+}
+
+/// Sends our Hello message as the first encrypted frame
+///
+/// `listen_port` should be zero if we aren't listening for connections.
+async fn send_hello(
+    static_secret_key: &SecretKey,
+    listen_port: u16,
+    framed: &mut Framed<&mut TcpStream, RlpxCodec>,
+) -> Result<(), HandshakeError> {
+    let hello = Hello::new(&static_secret_key.public_key(), listen_port);
+
+    framed.send(P2pMessage::Hello(hello).encode()).await?;
+
+    Ok(())
+}
+
+/// Receives the peer's Hello message as the first encrypted frame
+///
+/// The frame is authenticated by the codec, which verifies its header and frame MACs.
+/// The node ID in the Hello must match `peer_public_key`, which the handshake authenticated.
+/// On a mismatch, we send a Disconnect message with the reason `UnexpectedIdentity`.
+///
+/// # Errors
+/// - [`HandshakeError::CodecError`], if the frame fails authentication
+/// - [`HandshakeError::Disconnected`], if the peer sends a Disconnect message instead
+/// - [`HandshakeError::ConnectionClosed`], if the peer closes the connection instead
+/// - [`HandshakeError::ReadTimeout`], if the frame doesn't arrive within `read_timeout`
+/// - [`HandshakeError::UnexpectedIdentity`], if the Hello's node ID isn't the peer's
+async fn receive_hello(
+    framed: &mut Framed<&mut TcpStream, RlpxCodec>,
+    read_timeout: Duration,
+    peer_public_key: &PublicKey,
+) -> Result<Hello, HandshakeError> {
+    let frame_data = tokio::time::timeout(read_timeout, framed.next())
+        .await
+        .map_err(|_| HandshakeError::ReadTimeout)?
+        .ok_or(HandshakeError::ConnectionClosed)??;
+
+    let hello = match P2pMessage::decode(&frame_data)? {
+        P2pMessage::Hello(hello) => hello,
+        P2pMessage::Disconnect(reason) => return Err(HandshakeError::Disconnected(reason)),
+    };
+
+    if hello.node_id.as_bytes() != NodeId::from(peer_public_key).as_bytes() {
+        // both Hellos have been sent by now, so a p2p v5 peer expects compressed messages;
+        // the peer is already misbehaving, so a failure to tell it why doesn't matter
+        let disconnect = P2pMessage::Disconnect(DisconnectReason::UnexpectedIdentity);
+        let frame_data = if hello.protocol_version >= SNAPPY_PROTOCOL_VERSION {
+            disconnect.encode_compressed()
+        } else {
+            disconnect.encode()
+        };
+        let _ = framed.send(frame_data).await;
+
+        return Err(HandshakeError::UnexpectedIdentity(hex::encode(
+            hello.node_id,
+        )));
+    }
+
+    Ok(hello)
+}
+
+/// The decoded `auth-ack` message body
+///
+/// `ack-body = [recipient-ephemeral-pubk, recipient-nonce, ack-vsn, ...]`
+#[derive(Debug)]
+struct AuthAck {
+    recipient_ephemeral_public_key: PublicKey,
+    recipient_nonce: H256,
+    ack_version: u64,
+}
+
+/// Decodes a decrypted `auth-ack` message body
+///
+/// As required by EIP-8, additional list elements and trailing padding are ignored.
+///
+/// # Errors
+/// - [`HandshakeError::RlpDecodeError`], if the body isn't a valid RLP list
+/// - [`HandshakeError::Sec1Error`], if the recipient's ephemeral public key is invalid
+fn decode_auth_ack(ack_body: &[u8]) -> Result<AuthAck, HandshakeError> {
+    let rlp = Rlp::new(strip_padding(ack_body)?);
+
+    let recipient_ephemeral_public_key = public_key_from_bytes(rlp.at(0)?.data()?)?;
+    let recipient_nonce: H256 = rlp.val_at(1)?;
+    let ack_version: u64 = rlp.val_at(2)?;
+
+    Ok(AuthAck {
+        recipient_ephemeral_public_key,
+        recipient_nonce,
+        ack_version,
+    })
+}
+
+/// Pads, encrypts and size-prefixes a handshake message body, as required by EIP-8
+///
+/// `message = size || ecies.encrypt(remote-pubk, body || padding, size)`,
+/// where the 2-byte `size` is also used as the shared MAC data of ECIES.
+///
+/// # Returns
+/// The message, as it should be sent over the wire
+fn seal_eip8_message(
+    remote_public_key: &PublicKey,
+    body: &[u8],
+) -> Result<Vec<u8>, HandshakeError> {
+    // EIP-8: the body is followed by random padding
+    let mut body = body.to_vec();
+    append_padding(&mut body);
+
+    let size = u16::try_from(body.len() + ECIES_OVERHEAD)
+        .map_err(|_| HandshakeError::MessageTooLarge(body.len() + ECIES_OVERHEAD))?
+        .to_be_bytes();
+    let enc_body = ecies::encrypt(remote_public_key, &body, &size);
+
+    body.zeroize();
+
+    let enc_body = enc_body?;
+
+    let mut message = Vec::with_capacity(EIP8_SIZE_PREFIX_LEN + enc_body.len());
+    message.extend_from_slice(&size);
+    message.extend_from_slice(&enc_body);
+
+    Ok(message)
+}
+
+/// Decrypts a size-prefixed EIP-8 handshake message with our static key
+///
+/// # Returns
+/// The decrypted body, which is still followed by its padding
+fn open_eip8_message(
+    static_secret_key: &SecretKey,
+    message: &[u8],
+) -> Result<Vec<u8>, HandshakeError> {
+    let (size, enc_body) = message.split_at(EIP8_SIZE_PREFIX_LEN);
+
+    Ok(ecies::decrypt(static_secret_key, enc_body, size)?)
+}
+
+/// Reads a whole size-prefixed EIP-8 handshake message from the stream
+///
+/// Each of the two reads, of the size prefix and of the rest of the message,
+/// must complete within `read_timeout`.
+///
+/// # Returns
+/// The message, including its 2-byte size prefix
+///
+/// # Errors
+/// - [`HandshakeError::ReadTimeout`], if a read doesn't complete in time
+async fn read_eip8_message(
+    stream: &mut TcpStream,
+    read_timeout: Duration,
+) -> Result<Vec<u8>, HandshakeError> {
+    let mut size = [0u8; EIP8_SIZE_PREFIX_LEN];
+    tokio::time::timeout(read_timeout, stream.read_exact(&mut size))
+        .await
+        .map_err(|_| HandshakeError::ReadTimeout)??;
+
+    let mut message = vec![0u8; EIP8_SIZE_PREFIX_LEN + u16::from_be_bytes(size) as usize];
+    message[..EIP8_SIZE_PREFIX_LEN].copy_from_slice(&size);
+    tokio::time::timeout(
+        read_timeout,
+        stream.read_exact(&mut message[EIP8_SIZE_PREFIX_LEN..]),
+    )
+    .await
+    .map_err(|_| HandshakeError::ReadTimeout)??;
+
+    Ok(message)
+}
+
+/// Strips the EIP-8 padding that follows an RLP-encoded message body
+fn strip_padding(body: &[u8]) -> Result<&[u8], HandshakeError> {
+    let len = PayloadInfo::from(body)?.total();
+
+    Ok(body.get(..len).ok_or(DecoderError::RlpIsTooShort)?)
+}
+
+/// Converts a 64-byte public key, as used in RLPx, into a [`PublicKey`]
+///
+/// The RLPx form is an uncompressed SEC1 point without its leading `0x04` byte.
+///
+/// # Errors
+/// - [`HandshakeError::Sec1Error`], if the bytes don't represent a valid public key
+fn public_key_from_bytes(bytes: &[u8]) -> Result<PublicKey, HandshakeError> {
+    NodeId::from_slice(bytes)
+        .map(|node_id| node_id.public_key())
+        .map_err(|err| HandshakeError::Sec1Error(err.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::net::TcpListener;
+
+    use crate::constants::READ_TIMEOUT;
+
+    use super::*;
+
+    /// Test vectors from EIP-8
+    const STATIC_KEY_A: &str = "49a7b37aa6f6645917e7b807e9d1c00d4fa71f18343b0d4122a4d2df64dd6fee";
+    const STATIC_KEY_B: &str = "b71c71a67e1177ad4e901695e1b4b9ee17ae16c6668d313eac2f96dbcda3f291";
+    const EPHEMERAL_KEY_A: &str =
+        "869d6ecf5211f1cc60418a13b9d870b22959d0c16f02bec714c960dd2298a32d";
+    const EPHEMERAL_KEY_B: &str =
+        "e238eb8e04fee6511ab04c6dd3c89ce097b11f25d584863ac2b6d5b35b1847e4";
+    const NONCE_A: &str = "7e968bba13b6c50e2c4cd7f241cc0d64d1ac25c7f5952df231ac6a2bda8ee5d6";
+    const NONCE_B: &str = "559aead08264d5795d3909718cdd05abd49572e84fe55590eef31a88a08fdffd";
+    /// `auth` in the EIP-8 format, version 4, encrypted to key B
+    const AUTH_2: &str =
+        "01b304ab7578555167be8154d5cc456f567d5ba302662433674222360f08d5f1534499d3678b513b\
+        0fca474f3a514b18e75683032eb63fccb16c156dc6eb2c0b1593f0d84ac74f6e475f1b8d56116b84\
+        9634a8c458705bf83a626ea0384d4d7341aae591fae42ce6bd5c850bfe0b999a694a49bbbaf3ef6c\
+        da61110601d3b4c02ab6c30437257a6e0117792631a4b47c1d52fc0f8f89caadeb7d02770bf999cc\
+        147d2df3b62e1ffb2c9d8c125a3984865356266bca11ce7d3a688663a51d82defaa8aad69da39ab6\
+        d5470e81ec5f2a7a47fb865ff7cca21516f9299a07b1bc63ba56c7a1a892112841ca44b6e0034dee\
+        70c9adabc15d76a54f443593fafdc3b27af8059703f88928e199cb122362a4b35f62386da7caad09\
+        c001edaeb5f8a06d2b26fb6cb93c52a9fca51853b68193916982358fe1e5369e249875bb8d0d0ec3\
+        6f917bc5e1eafd5896d46bd61ff23f1a863a8a8dcd54c7b109b771c8e61ec9c8908c733c0263440e\
+        2aa067241aaa433f0bb053c7b31a838504b148f570c0ad62837129e547678c5190341e4f1693956c\
+        3bf7678318e2d5b5340c9e488eefea198576344afbdf66db5f51204a6961a63ce072c8926c";
+    /// `auth` in the EIP-8 format, version 56, with additional list elements, encrypted to key B
+    const AUTH_3: &str =
+        "01b8044c6c312173685d1edd268aa95e1d495474c6959bcdd10067ba4c9013df9e40ff45f5bfd6f7\
+        2471f93a91b493f8e00abc4b80f682973de715d77ba3a005a242eb859f9a211d93a347fa64b597bf\
+        280a6b88e26299cf263b01b8dfdb712278464fd1c25840b995e84d367d743f66c0e54a586725b7bb\
+        f12acca27170ae3283c1073adda4b6d79f27656993aefccf16e0d0409fe07db2dc398a1b7e8ee93b\
+        cd181485fd332f381d6a050fba4c7641a5112ac1b0b61168d20f01b479e19adf7fdbfa0905f63352\
+        bfc7e23cf3357657455119d879c78d3cf8c8c06375f3f7d4861aa02a122467e069acaf513025ff19\
+        6641f6d2810ce493f51bee9c966b15c5043505350392b57645385a18c78f14669cc4d960446c1757\
+        1b7c5d725021babbcd786957f3d17089c084907bda22c2b2675b4378b114c601d858802a55345a15\
+        116bc61da4193996187ed70d16730e9ae6b3bb8787ebcaea1871d850997ddc08b4f4ea668fbf3740\
+        7ac044b55be0908ecb94d4ed172ece66fd31bfdadf2b97a8bc690163ee11f5b575a4b44e36e2bfb2\
+        f0fce91676fd64c7773bac6a003f481fddd0bae0a1f31aa27504e2a533af4cef3b623f4791b2cca6\
+        d490";
+    /// `auth-ack` in the EIP-8 format, version 4, encrypted to key A
+    const ACK_2: &str =
+        "01ea0451958701280a56482929d3b0757da8f7fbe5286784beead59d95089c217c9b917788989470\
+        b0e330cc6e4fb383c0340ed85fab836ec9fb8a49672712aeabbdfd1e837c1ff4cace34311cd7f4de\
+        05d59279e3524ab26ef753a0095637ac88f2b499b9914b5f64e143eae548a1066e14cd2f4bd7f814\
+        c4652f11b254f8a2d0191e2f5546fae6055694aed14d906df79ad3b407d94692694e259191cde171\
+        ad542fc588fa2b7333313d82a9f887332f1dfc36cea03f831cb9a23fea05b33deb999e85489e645f\
+        6aab1872475d488d7bd6c7c120caf28dbfc5d6833888155ed69d34dbdc39c1f299be1057810f34fb\
+        e754d021bfca14dc989753d61c413d261934e1a9c67ee060a25eefb54e81a4d14baff922180c395d\
+        3f998d70f46f6b58306f969627ae364497e73fc27f6d17ae45a413d322cb8814276be6ddd13b885b\
+        201b943213656cde498fa0e9ddc8e0b8f8a53824fbd82254f3e2c17e8eaea009c38b4aa0a3f306e8\
+        797db43c25d68e86f262e564086f59a2fc60511c42abfb3057c247a8a8fe4fb3ccbadde17514b7ac\
+        8000cdb6a912778426260c47f38919a91f25f4b5ffb455d6aaaf150f7e5529c100ce62d6d92826a7\
+        1778d809bdf60232ae21ce8a437eca8223f45ac37f6487452ce626f549b3b5fdee26afd2072e4bc7\
+        5833c2464c805246155289f4";
+    /// `auth-ack` in the EIP-8 format, version 57, with additional list elements, encrypted to key A
+    const ACK_3: &str =
+        "01f004076e58aae772bb101ab1a8e64e01ee96e64857ce82b1113817c6cdd52c09d26f7b90981cd7\
+        ae835aeac72e1573b8a0225dd56d157a010846d888dac7464baf53f2ad4e3d584531fa203658fab0\
+        3a06c9fd5e35737e417bc28c1cbf5e5dfc666de7090f69c3b29754725f84f75382891c561040ea1d\
+        dc0d8f381ed1b9d0d4ad2a0ec021421d847820d6fa0ba66eaf58175f1b235e851c7e2124069fbc20\
+        2888ddb3ac4d56bcbd1b9b7eab59e78f2e2d400905050f4a92dec1c4bdf797b3fc9b2f8e84a482f3\
+        d800386186712dae00d5c386ec9387a5e9c9a1aca5a573ca91082c7d68421f388e79127a5177d4f8\
+        590237364fd348c9611fa39f78dcdceee3f390f07991b7b47e1daa3ebcb6ccc9607811cb17ce51f1\
+        c8c2c5098dbdd28fca547b3f58c01a424ac05f869f49c6a34672ea2cbbc558428aa1fe48bbfd6115\
+        8b1b735a65d99f21e70dbc020bfdface9f724a0d1fb5895db971cc81aa7608baa0920abb0a565c9c\
+        436e2fd13323428296c86385f2384e408a31e104670df0791d93e743a3a5194ee6b076fb6323ca59\
+        3011b7348c16cf58f66b9633906ba54a2ee803187344b394f75dd2e663a57b956cb830dd7a908d4f\
+        39a2336a61ef9fda549180d4ccde21514d117b6c6fd07a9102b5efe710a32af4eeacae2cb3b1dec0\
+        35b9593b48b9d3ca4c13d245d5f04169b0b1";
+
+    fn secret_key(hex_key: &str) -> SecretKey {
+        SecretKey::from_slice(&hex::decode(hex_key).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_decode_auth_ack_pass() {
+        let recipient_ephemeral_public_key = SecretKey::random(&mut OsRng).public_key();
+        let recipient_nonce = H256::random();
+
+        // EIP-8 allows for additional list elements, and padding
+        let mut rlp_stream = RlpStream::new_list(4);
+        rlp_stream.append(
+            &NodeId::from(&recipient_ephemeral_public_key)
+                .as_bytes()
+                .as_ref(),
+        );
+        rlp_stream.append(&recipient_nonce);
+        rlp_stream.append(&AUTH_VERSION);
+        rlp_stream.append(&"extra");
+        let mut ack_body = rlp_stream.out().to_vec();
+        append_padding(&mut ack_body);
+
+        let ack = decode_auth_ack(&ack_body).unwrap();
+
+        assert_eq!(
+            recipient_ephemeral_public_key,
+            ack.recipient_ephemeral_public_key
+        );
+        assert_eq!(recipient_nonce, ack.recipient_nonce);
+        assert_eq!(AUTH_VERSION as u64, ack.ack_version);
+    }
+
+    #[test]
+    fn test_decode_auth_ack_fail_bad_public_key() {
+        let mut rlp_stream = RlpStream::new_list(3);
+        rlp_stream.append(&[4u8; 10].as_ref());
+        rlp_stream.append(&H256::random());
+        rlp_stream.append(&AUTH_VERSION);
+        let ack_body = rlp_stream.out();
+
+        let result = decode_auth_ack(&ack_body);
+
+        assert!(matches!(result, Err(HandshakeError::Sec1Error(_))));
+    }
+
+    #[test]
+    fn test_open_eip8_auth_vectors() {
+        let key_a = secret_key(STATIC_KEY_A);
+        let key_b = secret_key(STATIC_KEY_B);
+        let nonce_a: H256 = NONCE_A.parse().unwrap();
+
+        for (auth, version) in [(AUTH_2, 4), (AUTH_3, 56)] {
+            let auth_body = open_eip8_message(&key_b, &hex::decode(auth).unwrap()).unwrap();
+
+            let (signature, initiator_public_key, initiator_nonce, auth_version) =
+                decode_auth_body(&auth_body).unwrap();
+
+            assert_eq!(key_a.public_key(), initiator_public_key);
+            assert_eq!(nonce_a, initiator_nonce);
+            assert_eq!(version, auth_version);
+
+            // the signature recovers the initiator's ephemeral key, as in Step 2
+            let static_shared_secret =
+                diffie_hellman(key_b.to_nonzero_scalar(), initiator_public_key.as_affine());
+            let message = H256::from_slice(static_shared_secret.raw_secret_bytes()) ^ nonce_a;
+            assert_eq!(
+                secret_key(EPHEMERAL_KEY_A).public_key(),
+                recover(&signature, &message).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_open_eip8_ack_vectors() {
+        let key_a = secret_key(STATIC_KEY_A);
+        let nonce_b: H256 = NONCE_B.parse().unwrap();
+
+        for (ack, version) in [(ACK_2, 4), (ACK_3, 57)] {
+            let ack_body = open_eip8_message(&key_a, &hex::decode(ack).unwrap()).unwrap();
+
+            let ack = decode_auth_ack(&ack_body).unwrap();
+
+            assert_eq!(
+                secret_key(EPHEMERAL_KEY_B).public_key(),
+                ack.recipient_ephemeral_public_key
+            );
+            assert_eq!(nonce_b, ack.recipient_nonce);
+            assert_eq!(version, ack.ack_version);
+        }
+    }
+
+    #[test]
+    fn test_append_padding() {
+        for _ in 0..100 {
+            let mut body = vec![1u8; 10];
+
+            append_padding(&mut body);
+
+            assert!(body.len() >= 10 + EIP8_PADDING_MIN);
+            assert!(body.len() <= 10 + EIP8_PADDING_MAX);
+            assert!(body.starts_with(&[1u8; 10]));
+        }
+    }
+
+    #[test]
+    fn test_sign_recoverable() {
+        let secret_key = NonZeroScalar::random(&mut OsRng);
+        let message = H256::random();
+
+        let signature = sign(&secret_key, &message).unwrap();
+
+        let recovered = recover(&signature, &message).unwrap();
+
+        assert_eq!(PublicKey::from_secret_scalar(&secret_key), recovered);
+    }
+
+    #[test]
+    fn test_recover_fail_wrong_message() {
+        let secret_key = NonZeroScalar::random(&mut OsRng);
+        let message = H256::random();
+
+        let signature = sign(&secret_key, &message).unwrap();
+        let recovered = recover(&signature, &H256::random());
+
+        assert_ne!(
+            Some(PublicKey::from_secret_scalar(&secret_key)),
+            recovered.ok()
+        );
+    }
+
+    #[test]
+    fn test_seal_open_eip8_message() {
+        let secret_key = SecretKey::random(&mut OsRng);
+        let body = b"body";
+
+        let message = seal_eip8_message(&secret_key.public_key(), body).unwrap();
+        let size = u16::from_be_bytes([message[0], message[1]]) as usize;
+        assert_eq!(message.len() - EIP8_SIZE_PREFIX_LEN, size);
+
+        let opened = open_eip8_message(&secret_key, &message).unwrap();
+        assert!(opened.starts_with(body));
+
+        let mut tampered = message.clone();
+        tampered[1] ^= 0x01;
+        assert!(open_eip8_message(&secret_key, &tampered).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_handshake_initiator_and_recipient() {
+        let read_timeout = Duration::from_millis(READ_TIMEOUT);
+        let initiator_key = SecretKey::random(&mut OsRng);
+        let recipient_key = SecretKey::random(&mut OsRng);
+        let recipient_public_key = recipient_key.public_key();
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+
+        let recipient = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            respond_to_handshake(&recipient_key, &mut stream, address.port(), read_timeout).await
+        });
+
+        let mut stream = TcpStream::connect(address).await.unwrap();
+        let hello = initiate_handshake(
+            &initiator_key,
+            &mut stream,
+            0,
+            read_timeout,
+            &recipient_public_key,
+            address.to_string(),
+        )
+        .await
+        .unwrap();
+
+        let (initiator_public_key, initiator_hello) = recipient.await.unwrap().unwrap();
+        assert_eq!(0, initiator_hello.listen_port);
+
+        assert_eq!(
+            *NodeId::from(&recipient_public_key).as_bytes(),
+            hello.node_id.as_bytes()
+        );
+        assert_eq!(address.port(), hello.listen_port);
+        assert_eq!(initiator_key.public_key(), initiator_public_key);
+        assert_eq!(
+            *NodeId::from(&initiator_public_key).as_bytes(),
+            initiator_hello.node_id.as_bytes()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_handshake_wrong_recipient_public_key() {
+        let read_timeout = Duration::from_millis(READ_TIMEOUT);
+        let recipient_key = SecretKey::random(&mut OsRng);
+        let wrong_public_key = SecretKey::random(&mut OsRng).public_key();
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+
+        let recipient = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            respond_to_handshake(&recipient_key, &mut stream, address.port(), read_timeout).await
+        });
+
+        let mut stream = TcpStream::connect(address).await.unwrap();
+        let result = initiate_handshake(
+            &SecretKey::random(&mut OsRng),
+            &mut stream,
+            0,
+            read_timeout,
+            &wrong_public_key,
+            address.to_string(),
+        )
+        .await;
+
+        assert!(matches!(result, Err(HandshakeError::AuthRejected)));
+        assert!(recipient.await.unwrap().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_handshake_closed_mid_ack() {
+        let read_timeout = Duration::from_millis(READ_TIMEOUT);
+        let recipient_key = SecretKey::random(&mut OsRng);
+        let recipient_public_key = recipient_key.public_key();
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+
+        // the recipient reads the auth, and closes after a part of the auth-ack's size prefix
+        let recipient = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            read_eip8_message(&mut stream, read_timeout).await.unwrap();
+            stream.write_all(&[0x01]).await.unwrap();
+        });
+
+        let mut stream = TcpStream::connect(address).await.unwrap();
+        let result = initiate_handshake(
+            &SecretKey::random(&mut OsRng),
+            &mut stream,
+            0,
+            read_timeout,
+            &recipient_public_key,
+            address.to_string(),
+        )
+        .await;
+        recipient.await.unwrap();
+
+        assert!(matches!(result, Err(HandshakeError::ConnectionClosed)));
+    }
+
+    #[tokio::test]
+    async fn test_handshake_unexpected_identity() {
+        let read_timeout = Duration::from_millis(READ_TIMEOUT);
+        let initiator_key = SecretKey::random(&mut OsRng);
+        let impersonated_key = SecretKey::random(&mut OsRng);
+        let recipient_key = SecretKey::random(&mut OsRng);
+        let recipient_public_key = recipient_key.public_key();
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+
+        let recipient = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            respond_to_handshake(&recipient_key, &mut stream, address.port(), read_timeout).await
+        });
+
+        // the initiator authenticates with its own key, but claims another node ID in its Hello
+        let hostname = address.to_string();
+        let mut stream = TcpStream::connect(address).await.unwrap();
+        let sent_auth = step_1(
+            &initiator_key,
+            &mut stream,
+            &recipient_public_key,
+            &hostname,
+        )
+        .await
+        .unwrap();
+        let secrets = step_5(
+            &initiator_key,
+            &mut stream,
+            &hostname,
+            read_timeout,
+            sent_auth,
+        )
+        .await
+        .unwrap();
+        let mut framed = Framed::new(&mut stream, RlpxCodec::new(secrets));
+        send_hello(&impersonated_key, 0, &mut framed).await.unwrap();
+
+        assert!(matches!(
+            recipient.await.unwrap(),
+            Err(HandshakeError::UnexpectedIdentity(node_id))
+                if node_id == NodeId::from(&impersonated_key.public_key()).to_string()
+        ));
+
+        // the recipient's Hello, then its Disconnect, compressed as our Hello is p2p v5
+        receive_hello(&mut framed, read_timeout, &recipient_public_key)
+            .await
+            .unwrap();
+        let frame_data = framed.next().await.unwrap().unwrap();
+        assert_eq!(
+            P2pMessage::Disconnect(DisconnectReason::UnexpectedIdentity).encode_compressed(),
+            frame_data
+        );
+    }
+}
@@ -0,0 +1,435 @@
+//! Encrypted keystores in the Web3 Secret Storage Definition, version 3
+//!
+//! The definition is at:
+//! https://ethereum.org/en/developers/docs/data-structures-and-encoding/web3-secret-storage/
+//!
+//! The secret key is encrypted with AES-128-CTR, under a key that is derived
+//! from a passphrase with scrypt or PBKDF2, and authenticated with a Keccak-256 MAC.
+
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use aes::cipher::{KeyIvInit, StreamCipher};
+use aes::Aes128;
+use k256::{NonZeroScalar, SecretKey};
+use rand_core::{OsRng, RngCore};
+use secrecy::zeroize::Zeroizing;
+use secrecy::{ExposeSecret, Secret, SecretString};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use sha3::{Digest, Keccak256};
+use subtle::ConstantTimeEq;
+
+use crate::constants::{
+    AES_128_KEY_LEN, ECIES_IV_LEN, KEYSTORE_CIPHER, KEYSTORE_DKLEN, KEYSTORE_PBKDF2_C,
+    KEYSTORE_PRF, KEYSTORE_SALT_LEN, KEYSTORE_SCRYPT_N, KEYSTORE_SCRYPT_P, KEYSTORE_SCRYPT_R,
+    KEYSTORE_VERSION,
+};
+use crate::errors::IdentityError;
+use crate::identity::create_private_file;
+
+type Aes128Ctr = ctr::Ctr128BE<Aes128>;
+
+/// A version 3 keystore, as stored in its JSON file
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Keystore {
+    // older geth versions capitalize it
+    #[serde(alias = "Crypto")]
+    pub crypto: KeystoreCrypto,
+    pub id: String,
+    pub version: u32,
+}
+
+/// The encrypted key and the parameters that are needed to decrypt it
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct KeystoreCrypto {
+    pub cipher: String,
+    pub cipherparams: CipherParams,
+    pub ciphertext: String,
+    pub kdf: String,
+    pub kdfparams: KdfParams,
+    pub mac: String,
+}
+
+/// The parameters of the cipher
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CipherParams {
+    pub iv: String,
+}
+
+/// The parameters of the key derivation function
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum KdfParams {
+    Scrypt {
+        dklen: usize,
+        n: u32,
+        r: u32,
+        p: u32,
+        salt: String,
+    },
+    Pbkdf2 {
+        c: u32,
+        dklen: usize,
+        prf: String,
+        salt: String,
+    },
+}
+
+/// The key derivation function to encrypt a new keystore with, and its cost parameters
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Kdf {
+    Scrypt { n: u32, r: u32, p: u32 },
+    Pbkdf2 { c: u32 },
+}
+
+impl Default for Kdf {
+    fn default() -> Self {
+        Self::Scrypt {
+            n: KEYSTORE_SCRYPT_N,
+            r: KEYSTORE_SCRYPT_R,
+            p: KEYSTORE_SCRYPT_P,
+        }
+    }
+}
+
+impl Kdf {
+    /// PBKDF2 with the standard iteration count
+    pub fn pbkdf2() -> Self {
+        Self::Pbkdf2 {
+            c: KEYSTORE_PBKDF2_C,
+        }
+    }
+}
+
+/// Where the passphrase of a keystore comes from
+#[derive(Clone, Debug, PartialEq)]
+pub enum PassphraseSource {
+    /// A file, whose first line is the passphrase
+    File(PathBuf),
+    /// An environment variable with the given name
+    Env(String),
+}
+
+/// Reads the passphrase of a keystore from its source
+///
+/// # Errors
+/// - [`IdentityError::PassphraseError`], if the file or the environment variable can't be read
+pub fn read_passphrase(source: &PassphraseSource) -> Result<SecretString, IdentityError> {
+    match source {
+        PassphraseSource::File(path) => {
+            let content = Zeroizing::new(fs::read_to_string(path).map_err(|err| {
+                IdentityError::PassphraseError(format!("{}: {}", path.display(), err))
+            })?);
+            let passphrase = content.lines().next().unwrap_or_default();
+
+            Ok(SecretString::new(passphrase.to_string()))
+        }
+        PassphraseSource::Env(name) => std::env::var(name)
+            .map(SecretString::new)
+            .map_err(|err| IdentityError::PassphraseError(format!("{}: {}", name, err))),
+    }
+}
+
+/// Loads a keystore from its JSON file
+///
+/// # Errors
+/// - [`IdentityError::ReadError`], if the file can't be read
+/// - [`IdentityError::InvalidKeystore`], if the file isn't a keystore
+pub fn load_keystore(path: &Path) -> Result<Keystore, IdentityError> {
+    let json = fs::read_to_string(path)
+        .map_err(|err| IdentityError::ReadError(path.to_path_buf(), err.to_string()))?;
+
+    serde_json::from_str(&json).map_err(|err| IdentityError::InvalidKeystore(err.to_string()))
+}
+
+/// Saves a keystore to a JSON file
+///
+/// The file must not exist yet. On Unix, it's readable and writable by its owner only.
+///
+/// # Errors
+/// - [`IdentityError::WriteError`], if the file exists, or can't be written
+pub fn save_keystore(path: &Path, keystore: &Keystore) -> Result<(), IdentityError> {
+    let write_error =
+        |err: std::io::Error| IdentityError::WriteError(path.to_path_buf(), err.to_string());
+
+    let json = serde_json::to_string_pretty(keystore)
+        .map_err(|err| IdentityError::InvalidKeystore(err.to_string()))?;
+
+    let mut file = create_private_file(path).map_err(write_error)?;
+    file.write_all(json.as_bytes()).map_err(write_error)
+}
+
+/// Decrypts the secret key in a keystore with its passphrase
+///
+/// # Errors
+/// - [`IdentityError::InvalidKeystore`], if the keystore is malformed or unsupported
+/// - [`IdentityError::WrongPassphrase`], if the MAC doesn't match
+pub fn unlock_keystore(
+    keystore: &Keystore,
+    passphrase: &SecretString,
+) -> Result<Secret<NonZeroScalar>, IdentityError> {
+    let crypto = &keystore.crypto;
+
+    if keystore.version != KEYSTORE_VERSION {
+        return Err(invalid(format!("Unsupported version {}", keystore.version)));
+    }
+    if crypto.cipher != KEYSTORE_CIPHER {
+        return Err(invalid(format!("Unsupported cipher {}", crypto.cipher)));
+    }
+
+    let derived_key = derive_key(passphrase, &crypto.kdf, &crypto.kdfparams)?;
+    let iv: [u8; ECIES_IV_LEN] = decode_hex(&crypto.cipherparams.iv, "IV")?
+        .try_into()
+        .map_err(|_| invalid("Invalid IV length".to_string()))?;
+    let ciphertext = decode_hex(&crypto.ciphertext, "ciphertext")?;
+    let expected_mac = decode_hex(&crypto.mac, "MAC")?;
+
+    if !bool::from(mac(&derived_key, &ciphertext).ct_eq(&expected_mac)) {
+        return Err(IdentityError::WrongPassphrase);
+    }
+
+    let mut plaintext = Zeroizing::new(ciphertext);
+    Aes128Ctr::new((&derived_key[..AES_128_KEY_LEN]).into(), &iv.into())
+        .apply_keystream(&mut plaintext);
+
+    let secret_key = SecretKey::from_slice(&plaintext)
+        .map_err(|err| IdentityError::InvalidKeystore(err.to_string()))?;
+
+    Ok(Secret::new(secret_key.to_nonzero_scalar()))
+}
+
+/// Encrypts a secret key into a new keystore with a passphrase
+///
+/// # Errors
+/// - [`IdentityError::InvalidKeystore`], if the KDF parameters are invalid
+pub fn encrypt_keystore(
+    secret_key: &SecretKey,
+    passphrase: &SecretString,
+    kdf: Kdf,
+) -> Result<Keystore, IdentityError> {
+    let mut salt = [0u8; KEYSTORE_SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    let salt = hex::encode(salt);
+
+    let (kdf, kdfparams) = match kdf {
+        Kdf::Scrypt { n, r, p } => (
+            "scrypt",
+            KdfParams::Scrypt {
+                dklen: KEYSTORE_DKLEN,
+                n,
+                r,
+                p,
+                salt,
+            },
+        ),
+        Kdf::Pbkdf2 { c } => (
+            "pbkdf2",
+            KdfParams::Pbkdf2 {
+                c,
+                dklen: KEYSTORE_DKLEN,
+                prf: KEYSTORE_PRF.to_string(),
+                salt,
+            },
+        ),
+    };
+
+    let derived_key = derive_key(passphrase, kdf, &kdfparams)?;
+
+    let mut iv = [0u8; ECIES_IV_LEN];
+    OsRng.fill_bytes(&mut iv);
+
+    let mut ciphertext = secret_key.to_bytes().to_vec();
+    Aes128Ctr::new((&derived_key[..AES_128_KEY_LEN]).into(), &iv.into())
+        .apply_keystream(&mut ciphertext);
+
+    let mac = mac(&derived_key, &ciphertext);
+
+    Ok(Keystore {
+        crypto: KeystoreCrypto {
+            cipher: KEYSTORE_CIPHER.to_string(),
+            cipherparams: CipherParams {
+                iv: hex::encode(iv),
+            },
+            ciphertext: hex::encode(ciphertext),
+            kdf: kdf.to_string(),
+            kdfparams,
+            mac: hex::encode(mac),
+        },
+        id: random_uuid(),
+        version: KEYSTORE_VERSION,
+    })
+}
+
+/// Derives the decryption key from the passphrase
+///
+/// The first half of the key is the AES-128 key, and the second half is the MAC key.
+fn derive_key(
+    passphrase: &SecretString,
+    kdf: &str,
+    kdfparams: &KdfParams,
+) -> Result<Zeroizing<[u8; KEYSTORE_DKLEN]>, IdentityError> {
+    let passphrase = passphrase.expose_secret().as_bytes();
+    let mut derived_key = Zeroizing::new([0u8; KEYSTORE_DKLEN]);
+
+    match kdfparams {
+        KdfParams::Scrypt {
+            dklen,
+            n,
+            r,
+            p,
+            salt,
+        } if kdf == "scrypt" => {
+            check_dklen(*dklen)?;
+            if !n.is_power_of_two() {
+                return Err(invalid(format!("scrypt n {} isn't a power of two", n)));
+            }
+            let params = scrypt::Params::new(n.trailing_zeros() as u8, *r, *p, KEYSTORE_DKLEN)
+                .map_err(|err| invalid(err.to_string()))?;
+            scrypt::scrypt(
+                passphrase,
+                &decode_hex(salt, "salt")?,
+                &params,
+                derived_key.as_mut(),
+            )
+            .map_err(|err| invalid(err.to_string()))?;
+        }
+        KdfParams::Pbkdf2 {
+            c,
+            dklen,
+            prf,
+            salt,
+        } if kdf == "pbkdf2" => {
+            check_dklen(*dklen)?;
+            if prf != KEYSTORE_PRF {
+                return Err(invalid(format!("Unsupported PRF {}", prf)));
+            }
+            pbkdf2::pbkdf2_hmac::<Sha256>(
+                passphrase,
+                &decode_hex(salt, "salt")?,
+                *c,
+                derived_key.as_mut(),
+            );
+        }
+        _ => return Err(invalid(format!("Unsupported KDF {}", kdf))),
+    }
+
+    Ok(derived_key)
+}
+
+/// Computes the MAC, i.e., `keccak256(derived-key[16..32] || ciphertext)`
+fn mac(derived_key: &[u8; KEYSTORE_DKLEN], ciphertext: &[u8]) -> [u8; 32] {
+    let mut hasher = Keccak256::new();
+    hasher.update(&derived_key[AES_128_KEY_LEN..]);
+    hasher.update(ciphertext);
+
+    hasher.finalize().into()
+}
+
+/// Generates a random (version 4) UUID, which identifies a keystore
+fn random_uuid() -> String {
+    let mut bytes = [0u8; 16];
+    OsRng.fill_bytes(&mut bytes);
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+
+    let hex = hex::encode(bytes);
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+fn check_dklen(dklen: usize) -> Result<(), IdentityError> {
+    if dklen != KEYSTORE_DKLEN {
+        return Err(invalid(format!("Unsupported derived key length {}", dklen)));
+    }
+
+    Ok(())
+}
+
+fn decode_hex(value: &str, name: &str) -> Result<Vec<u8>, IdentityError> {
+    hex::decode(value).map_err(|err| invalid(format!("Invalid {}: {}", name, err)))
+}
+
+fn invalid(reason: String) -> IdentityError {
+    IdentityError::InvalidKeystore(reason)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::constants::{
+        TEST_KEYSTORE_PASSPHRASE, TEST_KEYSTORE_PBKDF2, TEST_KEYSTORE_SECRET_KEY,
+    };
+    use crate::test_utils::with_env;
+
+    use super::*;
+
+    /// Cheap parameters, so that tests run fast
+    const LIGHT_SCRYPT: Kdf = Kdf::Scrypt {
+        n: 1 << 10,
+        r: 8,
+        p: 1,
+    };
+
+    fn passphrase(passphrase: &str) -> SecretString {
+        SecretString::new(passphrase.to_string())
+    }
+
+    #[test]
+    fn test_unlock_keystore_pbkdf2_vector() {
+        let keystore = load_keystore(Path::new(TEST_KEYSTORE_PBKDF2)).unwrap();
+        let secret = unlock_keystore(&keystore, &passphrase(TEST_KEYSTORE_PASSPHRASE)).unwrap();
+
+        assert_eq!(
+            TEST_KEYSTORE_SECRET_KEY,
+            hex::encode(secret.expose_secret().to_bytes())
+        );
+    }
+
+    #[test]
+    fn test_encrypt_unlock_keystore() {
+        let secret_key = SecretKey::random(&mut OsRng);
+
+        for kdf in [LIGHT_SCRYPT, Kdf::Pbkdf2 { c: 1024 }] {
+            let keystore = encrypt_keystore(&secret_key, &passphrase("secret"), kdf).unwrap();
+
+            let json = serde_json::to_string(&keystore).unwrap();
+            let keystore: Keystore = serde_json::from_str(&json).unwrap();
+
+            let unlocked = unlock_keystore(&keystore, &passphrase("secret")).unwrap();
+            assert_eq!(secret_key.to_bytes(), unlocked.expose_secret().to_bytes());
+        }
+    }
+
+    #[test]
+    fn test_unlock_keystore_wrong_passphrase() {
+        let secret_key = SecretKey::random(&mut OsRng);
+        let keystore = encrypt_keystore(&secret_key, &passphrase("secret"), LIGHT_SCRYPT).unwrap();
+
+        assert!(matches!(
+            unlock_keystore(&keystore, &passphrase("wrong")),
+            Err(IdentityError::WrongPassphrase)
+        ));
+    }
+
+    #[test]
+    fn test_read_passphrase_env() {
+        let name = "ETHEREUM_HANDSHAKE_TEST_PASSPHRASE";
+
+        let (passphrase, missing) = with_env(name, "secret", || {
+            (
+                read_passphrase(&PassphraseSource::Env(name.to_string())),
+                read_passphrase(&PassphraseSource::Env(format!("{}_MISSING", name))),
+            )
+        });
+
+        assert_eq!("secret", passphrase.unwrap().expose_secret());
+        assert!(missing.is_err());
+    }
+}
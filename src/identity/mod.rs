@@ -0,0 +1,175 @@
+//! # Module [`identity`]
+//!
+//! Our node's identity, i.e., its static secp256k1 secret key,
+//! whose public key is our node ID.
+//!
+//! Supported sources are:
+//! - Random, i.e., a new identity on every run
+//! - Node key file in the geth format, which is generated when it doesn't exist
+//! - Node key in the geth format, given directly as hex
+//! - Encrypted keystore in the Web3 Secret Storage format, unlocked with a passphrase
+
+pub use keystore::*;
+pub use node_id::*;
+pub use nodekey::*;
+
+mod keystore;
+mod node_id;
+mod nodekey;
+
+use std::fs::{self, File, OpenOptions};
+use std::io;
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+
+use k256::SecretKey;
+use rand_core::OsRng;
+use secrecy::{ExposeSecret, SecretString};
+
+#[cfg(unix)]
+use crate::constants::NODEKEY_FILE_MODE;
+use crate::errors::IdentityError;
+
+/// Where our node's static secret key comes from
+#[derive(Debug, Default)]
+pub enum KeySource {
+    /// A new random key, so our node ID changes on every run
+    #[default]
+    Random,
+    /// A geth-style node key file, which is generated if it doesn't exist
+    NodeKeyFile(PathBuf),
+    /// A geth-style hex-encoded node key
+    NodeKeyHex(SecretString),
+    /// An encrypted keystore file, and where its passphrase comes from
+    Keystore(PathBuf, PassphraseSource),
+}
+
+/// Loads our node's static secret key from its source
+///
+/// A key that is unlocked from a keystore is kept in a [`secrecy::Secret`]
+/// until it's turned into a [`SecretKey`], which zeroizes itself when dropped.
+///
+/// # Errors
+/// - [`IdentityError`], if the key can't be read, written, or is invalid
+pub fn load_static_secret_key(source: &KeySource) -> Result<SecretKey, IdentityError> {
+    match source {
+        KeySource::Random => Ok(SecretKey::random(&mut OsRng)),
+        KeySource::NodeKeyFile(path) => load_or_generate_nodekey(path),
+        KeySource::NodeKeyHex(hex) => parse_nodekey_hex(hex.expose_secret()),
+        KeySource::Keystore(path, passphrase) => {
+            let keystore = load_keystore(path)?;
+            let secret = unlock_keystore(&keystore, &read_passphrase(passphrase)?)?;
+
+            Ok(SecretKey::from(*secret.expose_secret()))
+        }
+    }
+}
+
+/// Generates a new static secret key, and saves it to its source
+///
+/// The node key file or the keystore file must not exist yet.
+///
+/// # Errors
+/// - [`IdentityError::NoKeyFile`], if the source isn't a node key file or a keystore
+/// - [`IdentityError`], if the passphrase can't be read, or the file can't be written
+pub fn generate_static_secret_key(source: &KeySource) -> Result<SecretKey, IdentityError> {
+    let secret_key = SecretKey::random(&mut OsRng);
+
+    match source {
+        KeySource::NodeKeyFile(path) => save_nodekey(path, &secret_key)?,
+        KeySource::Keystore(path, passphrase) => {
+            let keystore =
+                encrypt_keystore(&secret_key, &read_passphrase(passphrase)?, Kdf::default())?;
+            save_keystore(path, &keystore)?;
+        }
+        KeySource::Random | KeySource::NodeKeyHex(_) => return Err(IdentityError::NoKeyFile),
+    }
+
+    Ok(secret_key)
+}
+
+/// Creates a new file that is readable and writable by its owner only, on Unix
+///
+/// Missing parent directories are created.
+///
+/// # Errors
+/// - [`io::Error`], if the file exists, or can't be created
+pub(crate) fn create_private_file(path: &Path) -> io::Result<File> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    options.mode(NODEKEY_FILE_MODE);
+
+    options.open(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::constants::{
+        TEST_KEYSTORE_PASSPHRASE, TEST_KEYSTORE_PBKDF2, TEST_KEYSTORE_SECRET_KEY, TEST_NODEKEY,
+    };
+    use crate::test_utils::with_env;
+
+    use super::*;
+
+    #[test]
+    fn test_load_static_secret_key_from_hex() {
+        let source = KeySource::NodeKeyHex(SecretString::new(TEST_NODEKEY.to_string()));
+
+        let secret_key = load_static_secret_key(&source).unwrap();
+
+        assert_eq!(TEST_NODEKEY, hex::encode(secret_key.to_bytes()));
+    }
+
+    #[test]
+    fn test_load_static_secret_key_from_keystore() {
+        let name = "ETHEREUM_HANDSHAKE_TEST_KEYSTORE_PASSPHRASE";
+        let source = KeySource::Keystore(
+            PathBuf::from(TEST_KEYSTORE_PBKDF2),
+            PassphraseSource::Env(name.to_string()),
+        );
+
+        let secret_key = with_env(name, TEST_KEYSTORE_PASSPHRASE, || {
+            load_static_secret_key(&source)
+        })
+        .unwrap();
+
+        assert_eq!(TEST_KEYSTORE_SECRET_KEY, hex::encode(secret_key.to_bytes()));
+    }
+
+    #[test]
+    fn test_generate_static_secret_key() {
+        let path = std::env::temp_dir()
+            .join(format!("ethereum-handshake-{}", std::process::id()))
+            .join("keygen/nodekey");
+        let _ = fs::remove_file(&path);
+        let source = KeySource::NodeKeyFile(path.clone());
+
+        let generated = generate_static_secret_key(&source).unwrap();
+
+        assert_eq!(generated, load_static_secret_key(&source).unwrap());
+        assert!(matches!(
+            generate_static_secret_key(&source),
+            Err(IdentityError::WriteError(_, _))
+        ));
+        assert!(matches!(
+            generate_static_secret_key(&KeySource::Random),
+            Err(IdentityError::NoKeyFile)
+        ));
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_load_static_secret_key_random() {
+        let first = load_static_secret_key(&KeySource::Random).unwrap();
+        let second = load_static_secret_key(&KeySource::Random).unwrap();
+
+        assert_ne!(first, second);
+    }
+}
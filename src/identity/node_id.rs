@@ -0,0 +1,168 @@
+//! Node IDs
+//!
+//! A node ID is a node's static secp256k1 public key, in its 64-byte uncompressed form,
+//! i.e., without the leading SEC1 tag byte. It's the user name part of an enode.
+
+use std::fmt;
+use std::net::SocketAddr;
+use std::str::FromStr;
+
+use ethereum_types::H256;
+use k256::elliptic_curve::sec1::ToEncodedPoint;
+use k256::{PublicKey, SecretKey};
+use sha3::{Digest, Keccak256};
+
+use crate::constants::{NODE_ID_LEN, PUBLIC_KEY_UNCOMPRESSED_LEN};
+use crate::errors::NodeIdError;
+
+/// A node ID, which is always a valid public key
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NodeId([u8; NODE_ID_LEN]);
+
+impl NodeId {
+    /// Parses a node ID from its 64 bytes
+    ///
+    /// # Errors
+    /// - [`NodeIdError::InvalidLength`], if there aren't 64 bytes
+    /// - [`NodeIdError::InvalidPublicKey`], if the bytes don't represent a point on the curve
+    pub fn from_slice(bytes: &[u8]) -> Result<Self, NodeIdError> {
+        let bytes: [u8; NODE_ID_LEN] = bytes
+            .try_into()
+            .map_err(|_| NodeIdError::InvalidLength(bytes.len()))?;
+
+        let node_id = Self(bytes);
+        node_id.sec1_public_key()?;
+
+        Ok(node_id)
+    }
+
+    /// The node ID that belongs to a secret key
+    pub fn from_secret_key(secret_key: &SecretKey) -> Self {
+        Self::from(&secret_key.public_key())
+    }
+
+    /// The public key that this node ID represents
+    pub fn public_key(&self) -> PublicKey {
+        self.sec1_public_key()
+            .expect("A node ID is validated when it's created")
+    }
+
+    pub fn as_bytes(&self) -> &[u8; NODE_ID_LEN] {
+        &self.0
+    }
+
+    /// The node hash, i.e., `keccak256(node-id)`, which is used in node discovery
+    pub fn hash(&self) -> H256 {
+        H256::from_slice(&Keccak256::digest(self.0))
+    }
+
+    /// The enode URL of this node, at the given address
+    pub fn enode(&self, address: SocketAddr) -> String {
+        format!("enode://{}@{}", self, address)
+    }
+
+    fn sec1_public_key(&self) -> Result<PublicKey, NodeIdError> {
+        let mut sec1 = [0u8; PUBLIC_KEY_UNCOMPRESSED_LEN];
+        sec1[0] = 4;
+        sec1[1..].copy_from_slice(&self.0);
+
+        PublicKey::from_sec1_bytes(&sec1)
+            .map_err(|err| NodeIdError::InvalidPublicKey(err.to_string()))
+    }
+}
+
+impl From<&PublicKey> for NodeId {
+    fn from(public_key: &PublicKey) -> Self {
+        let mut bytes = [0u8; NODE_ID_LEN];
+        bytes.copy_from_slice(&public_key.to_encoded_point(false).as_bytes()[1..]);
+
+        Self(bytes)
+    }
+}
+
+impl FromStr for NodeId {
+    type Err = NodeIdError;
+
+    /// Parses a node ID from 128 hex characters
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.len() != 2 * NODE_ID_LEN {
+            return Err(NodeIdError::InvalidHexLength(s.len()));
+        }
+
+        let bytes = hex::decode(s).map_err(|err| NodeIdError::HexDecodeError(err.to_string()))?;
+
+        Self::from_slice(&bytes)
+    }
+}
+
+impl fmt::Display for NodeId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&hex::encode(self.0))
+    }
+}
+
+impl fmt::Debug for NodeId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "NodeId({})", self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand_core::OsRng;
+
+    use crate::constants::{
+        TEST_HOSTNAME, TEST_NODEKEY, TEST_NODEKEY_NODE_HASH, TEST_NODEKEY_NODE_ID, TEST_USERNAME,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_node_id_round_trip() {
+        let node_id: NodeId = TEST_USERNAME.parse().unwrap();
+
+        assert_eq!(TEST_USERNAME, node_id.to_string());
+        assert_eq!(node_id, NodeId::from(&node_id.public_key()));
+        assert_eq!(
+            format!("enode://{}@{}", TEST_USERNAME, TEST_HOSTNAME),
+            node_id.enode(TEST_HOSTNAME.parse().unwrap())
+        );
+    }
+
+    #[test]
+    fn test_node_id_from_secret_key() {
+        let secret_key = SecretKey::random(&mut OsRng);
+        let node_id = NodeId::from_secret_key(&secret_key);
+
+        assert_eq!(secret_key.public_key(), node_id.public_key());
+    }
+
+    #[test]
+    fn test_node_id_vectors() {
+        let secret_key = SecretKey::from_slice(&hex::decode(TEST_NODEKEY).unwrap()).unwrap();
+        let node_id = NodeId::from_secret_key(&secret_key);
+
+        assert_eq!(TEST_NODEKEY_NODE_ID, node_id.to_string());
+        assert_eq!(TEST_NODEKEY_NODE_HASH, hex::encode(node_id.hash()));
+    }
+
+    #[test]
+    fn test_node_id_invalid() {
+        assert_eq!(
+            Err(NodeIdError::InvalidHexLength(10)),
+            TEST_USERNAME[..10].parse::<NodeId>()
+        );
+        assert!(matches!(
+            TEST_USERNAME.replace('a', "z").parse::<NodeId>(),
+            Err(NodeIdError::HexDecodeError(_))
+        ));
+        assert_eq!(
+            Err(NodeIdError::InvalidLength(63)),
+            NodeId::from_slice(&[1u8; 63])
+        );
+        assert!(matches!(
+            NodeId::from_slice(&[0u8; NODE_ID_LEN]),
+            Err(NodeIdError::InvalidPublicKey(_))
+        ));
+    }
+}
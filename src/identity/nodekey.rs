@@ -0,0 +1,161 @@
+//! Node key files in the geth format
+//!
+//! A node key file holds the secret key as 64 hex characters, without a `0x` prefix.
+//! Surrounding whitespace, such as a trailing line break, is tolerated when reading.
+
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+use k256::SecretKey;
+use rand_core::OsRng;
+use secrecy::Zeroize;
+use tracing::info;
+
+use crate::constants::NODEKEY_HEX_LEN;
+use crate::errors::IdentityError;
+use crate::identity::create_private_file;
+
+/// Parses a hex-encoded node key in the geth format
+///
+/// # Errors
+/// - [`IdentityError::InvalidNodeKey`], if it isn't 64 hex characters, or isn't a valid secret key
+pub fn parse_nodekey_hex(nodekey: &str) -> Result<SecretKey, IdentityError> {
+    let nodekey = nodekey.trim();
+    if nodekey.len() != NODEKEY_HEX_LEN {
+        return Err(IdentityError::InvalidNodeKey(format!(
+            "Expected {} hex characters, got {}",
+            NODEKEY_HEX_LEN,
+            nodekey.len()
+        )));
+    }
+
+    let mut bytes =
+        hex::decode(nodekey).map_err(|err| IdentityError::InvalidNodeKey(err.to_string()))?;
+    let secret_key = SecretKey::from_slice(&bytes);
+    bytes.zeroize();
+
+    secret_key.map_err(|err| IdentityError::InvalidNodeKey(err.to_string()))
+}
+
+/// Loads a node key file in the geth format
+///
+/// # Errors
+/// - [`IdentityError::ReadError`], if the file can't be read
+/// - [`IdentityError::InvalidNodeKey`], if the file's content isn't a valid node key
+pub fn load_nodekey(path: &Path) -> Result<SecretKey, IdentityError> {
+    let mut nodekey = fs::read_to_string(path)
+        .map_err(|err| IdentityError::ReadError(path.to_path_buf(), err.to_string()))?;
+    let secret_key = parse_nodekey_hex(&nodekey);
+    nodekey.zeroize();
+
+    secret_key
+}
+
+/// Saves a node key file in the geth format
+///
+/// The file must not exist yet. On Unix, it's readable and writable by its owner only.
+/// Missing parent directories are created.
+///
+/// # Errors
+/// - [`IdentityError::WriteError`], if the file exists, or can't be written
+pub fn save_nodekey(path: &Path, secret_key: &SecretKey) -> Result<(), IdentityError> {
+    let write_error =
+        |err: std::io::Error| IdentityError::WriteError(path.to_path_buf(), err.to_string());
+
+    let mut file = create_private_file(path).map_err(write_error)?;
+    let mut nodekey = hex::encode(secret_key.to_bytes());
+    let written = file.write_all(nodekey.as_bytes());
+    nodekey.zeroize();
+
+    written.map_err(write_error)
+}
+
+/// Loads a node key file in the geth format, or generates and saves a new key
+/// if the file doesn't exist
+///
+/// # Errors
+/// - [`IdentityError`], if the file can't be read or written, or isn't a valid node key
+pub fn load_or_generate_nodekey(path: &Path) -> Result<SecretKey, IdentityError> {
+    if path.exists() {
+        return load_nodekey(path);
+    }
+
+    let secret_key = SecretKey::random(&mut OsRng);
+    save_nodekey(path, &secret_key)?;
+
+    info!("Generated a new node key at {}.", path.display());
+
+    Ok(secret_key)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::constants::{TEST_NODEKEY, TEST_NODEKEY_FILE};
+
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir()
+            .join(format!("ethereum-handshake-{}", std::process::id()))
+            .join(name)
+    }
+
+    #[test]
+    fn test_parse_nodekey_hex() {
+        let secret_key = parse_nodekey_hex(&format!("{}\n", TEST_NODEKEY)).unwrap();
+        assert_eq!(TEST_NODEKEY, hex::encode(secret_key.to_bytes()));
+
+        assert!(parse_nodekey_hex(&TEST_NODEKEY[1..]).is_err());
+        assert!(parse_nodekey_hex(&format!("0x{}", &TEST_NODEKEY[2..])).is_err());
+        assert!(parse_nodekey_hex(&"0".repeat(NODEKEY_HEX_LEN)).is_err());
+    }
+
+    #[test]
+    fn test_load_nodekey() {
+        let secret_key = load_nodekey(Path::new(TEST_NODEKEY_FILE)).unwrap();
+
+        assert_eq!(TEST_NODEKEY, hex::encode(secret_key.to_bytes()));
+    }
+
+    #[test]
+    fn test_load_or_generate_nodekey() {
+        let path = temp_path("generated/nodekey");
+        let _ = fs::remove_file(&path);
+
+        let generated = load_or_generate_nodekey(&path).unwrap();
+        let loaded = load_or_generate_nodekey(&path).unwrap();
+
+        assert_eq!(generated, loaded);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            use crate::constants::NODEKEY_FILE_MODE;
+
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(NODEKEY_FILE_MODE, mode & 0o777);
+        }
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_save_nodekey_existing() {
+        let path = temp_path("existing/nodekey");
+        let _ = fs::remove_file(&path);
+        let secret_key = SecretKey::random(&mut OsRng);
+
+        save_nodekey(&path, &secret_key).unwrap();
+
+        assert!(matches!(
+            save_nodekey(&path, &secret_key),
+            Err(IdentityError::WriteError(_, _))
+        ));
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,62 +1,821 @@
 //! The CLI arguments parser
+//!
+//! Every mode of operation is a subcommand with its own arguments.
+//! Arguments that several subcommands share are grouped in flattened structs.
+//!
+//! Every argument can also be set with an `ETH_HANDSHAKE_*` environment variable,
+//! or in a configuration file. Their precedence, from the highest, is:
+//! CLI, environment, configuration file, and the default value.
 
+use std::ffi::OsString;
+use std::net::SocketAddr;
 use std::path::PathBuf;
+use std::time::Duration;
 
-use clap::Parser;
+use clap::error::ErrorKind;
+use clap::parser::ValueSource;
+use clap::{ArgMatches, Args, CommandFactory, FromArgMatches, Parser, Subcommand};
+use secrecy::SecretString;
 
-use crate::constants::TIMEOUT;
-use crate::input::Enode;
+use crate::constants::{
+    BACKOFF_BASE, BACKOFF_MAX, CONCURRENCY, CONNECT_TIMEOUT, HANDSHAKE_TIMEOUT, LISTEN_ADDRESS,
+    MAX_INBOUND, MIN_TIMEOUT, READ_TIMEOUT, RETRIES,
+};
+use crate::identity::{KeySource, PassphraseSource};
+use crate::input::{load_config, Config, Enode};
+use crate::interface::Timeouts;
+use crate::output::OutputFormat;
+use crate::retry::RetryPolicy;
 
 /// An implementation of the Ethereum handshake procedure
 #[derive(Parser)]
 #[command(name = "Ethereum Handshake")]
 #[command(author, version, about, long_about = None)]
 struct CliArgs {
-    /// Handshake timeout in milliseconds, from 100 to 10000
-    #[arg(short, long, default_value_t = TIMEOUT,
-    value_parser = clap::value_parser!(u64).range(100..=10*TIMEOUT))]
-    pub timeout: u64,
+    #[command(subcommand)]
+    pub command: CliCommand,
 
+    /// Path to a TOML configuration file with the run options under the names
+    /// of the long options, e.g., `handshake-timeout = 2500`
+    #[arg(long, env = "ETH_HANDSHAKE_CONFIG", global = true)]
+    pub config: Option<PathBuf>,
+
+    /// Log filter, such as `info` or `debug`, which takes precedence over `RUST_LOG`
+    #[arg(long, env = "ETH_HANDSHAKE_LOG_LEVEL", global = true)]
+    pub log_level: Option<String>,
+}
+
+/// Subcommands, with their arguments
+#[derive(Subcommand)]
+enum CliCommand {
+    /// Dial the recipient enodes, and report the outcome of every handshake
+    Dial(DialArgs),
+    /// Listen for incoming connections, and respond to handshakes;
+    /// runs until interrupted (Ctrl+C), or until the deadline
+    Listen(ListenArgs),
+    /// Listen for incoming connections while dialing the recipient enodes;
+    /// the dial results are emitted once dialing is done, and listening
+    /// runs until interrupted (Ctrl+C), or until the deadline
+    Serve(ServeArgs),
+    /// Generate a new node key, save it to the node key file or to the keystore,
+    /// and print our enode
+    Keygen(NodeArgs),
+    /// Print our node ID, node hash and enode
+    Whoami(NodeArgs),
+    /// Dial a single recipient enode, and print everything that is learned about it
+    Inspect(InspectArgs),
+    /// Dial the recipient enodes, and print a census of their outcomes,
+    /// clients and capabilities; until node discovery exists, it's a one-hop
+    /// census, i.e., only the given recipients are dialed, not their peers
+    Crawl(DialerArgs),
+}
+
+/// Arguments of the `dial` subcommand
+#[derive(Args)]
+struct DialArgs {
+    #[command(flatten)]
+    pub dialer: DialerArgs,
+
+    #[command(flatten)]
+    pub output: OutputArgs,
+}
+
+/// Arguments of the `listen` subcommand
+#[derive(Args)]
+struct ListenArgs {
+    /// Address and port to listen on for incoming connections
+    #[arg(short, long, env = "ETH_HANDSHAKE_LISTEN_ADDRESS",
+    default_value = LISTEN_ADDRESS)]
+    pub listen_address: SocketAddr,
+
+    /// Maximum number of inbound handshakes to answer at the same time
+    #[arg(long, env = "ETH_HANDSHAKE_MAX_INBOUND",
+    default_value_t = MAX_INBOUND as u16, value_parser = clap::value_parser!(u16).range(1..))]
+    pub max_inbound: u16,
+
+    #[command(flatten)]
+    pub timeouts: HandshakeTimeoutArgs,
+
+    #[command(flatten)]
+    pub identity: IdentityArgs,
+}
+
+/// Arguments of the `serve` subcommand
+#[derive(Args)]
+struct ServeArgs {
+    /// Address and port to listen on for incoming connections
+    #[arg(short, long, env = "ETH_HANDSHAKE_LISTEN_ADDRESS",
+    default_value = LISTEN_ADDRESS)]
+    pub listen_address: SocketAddr,
+
+    /// Maximum number of inbound handshakes to answer at the same time
+    #[arg(long, env = "ETH_HANDSHAKE_MAX_INBOUND",
+    default_value_t = MAX_INBOUND as u16, value_parser = clap::value_parser!(u16).range(1..))]
+    pub max_inbound: u16,
+
+    #[command(flatten)]
+    pub dialer: DialerArgs,
+
+    #[command(flatten)]
+    pub output: OutputArgs,
+}
+
+/// Arguments of the `keygen` and `whoami` subcommands
+#[derive(Args)]
+struct NodeArgs {
+    /// Address and port of our node, which is a part of our enode
+    #[arg(short, long, env = "ETH_HANDSHAKE_LISTEN_ADDRESS",
+    default_value = LISTEN_ADDRESS)]
+    pub listen_address: SocketAddr,
+
+    #[command(flatten)]
+    pub identity: IdentityArgs,
+}
+
+/// Arguments of the `inspect` subcommand
+#[derive(Args)]
+struct InspectArgs {
+    /// The recipient enode in the following form:
+    /// enode://<node_id>@<ip_address>:<port>
+    pub enode: String,
+
+    #[command(flatten)]
+    pub timeouts: TimeoutArgs,
+
+    #[command(flatten)]
+    pub retry: RetryArgs,
+
+    #[command(flatten)]
+    pub resolver: ResolverArgs,
+
+    #[command(flatten)]
+    pub identity: IdentityArgs,
+}
+
+/// Arguments of the subcommands that dial a list of recipient enodes
+#[derive(Args)]
+struct DialerArgs {
+    #[command(flatten)]
+    pub recipients: RecipientArgs,
+
+    /// Maximum number of recipient nodes to dial at the same time
+    #[arg(short, long, env = "ETH_HANDSHAKE_CONCURRENCY",
+    default_value_t = CONCURRENCY as u16, value_parser = clap::value_parser!(u16).range(1..))]
+    pub concurrency: u16,
+
+    /// Percentage of failed handshakes, from 0 to 100, up to which
+    /// the program still exits successfully
+    #[arg(long, env = "ETH_HANDSHAKE_FAIL_THRESHOLD",
+    value_parser = clap::value_parser!(u8).range(0..=100))]
+    pub fail_threshold: Option<u8>,
+
+    #[command(flatten)]
+    pub timeouts: TimeoutArgs,
+
+    #[command(flatten)]
+    pub retry: RetryArgs,
+
+    #[command(flatten)]
+    pub resolver: ResolverArgs,
+
+    #[command(flatten)]
+    pub identity: IdentityArgs,
+}
+
+/// Where the recipient enodes come from; at least one source is required,
+/// which can also be in the configuration file
+#[derive(Args)]
+struct RecipientArgs {
     /// A list of recipient enodes in the following form:
-    /// enode://<node_id>@<ipv4_address>:<port>
-    #[arg(short, long)]
+    /// enode://<node_id>@<ip_address>:<port>
+    #[arg(
+        short,
+        long,
+        env = "ETH_HANDSHAKE_RECIPIENT_ENODES",
+        value_delimiter = ','
+    )]
     pub recipient_enodes: Option<Vec<String>>,
 
     /// Path to a text file with a list of recipient enodes in the following form:
-    /// enode://<node_id>@<ipv4_address>:<port>
-    #[arg(short, long)]
+    /// enode://<node_id>@<ip_address>:<port>
+    #[arg(short, long, env = "ETH_HANDSHAKE_FILE_PATH")]
     pub file_path: Option<PathBuf>,
 }
 
+/// Timeouts of dialing, in milliseconds
+#[derive(Args)]
+struct TimeoutArgs {
+    /// Timeout of establishing a TCP connection, in milliseconds, at least 100
+    #[arg(long, env = "ETH_HANDSHAKE_CONNECT_TIMEOUT", default_value_t = CONNECT_TIMEOUT,
+    value_parser = clap::value_parser!(u64).range(MIN_TIMEOUT..))]
+    pub connect_timeout: u64,
+
+    #[command(flatten)]
+    pub handshake: HandshakeTimeoutArgs,
+}
+
+/// Timeouts of a handshake once connected, in milliseconds,
+/// which are all the timeouts that listening takes
+#[derive(Args)]
+struct HandshakeTimeoutArgs {
+    /// Timeout of a whole handshake, once connected, in milliseconds, at least 100
+    #[arg(short = 't', long, env = "ETH_HANDSHAKE_HANDSHAKE_TIMEOUT", visible_alias = "timeout",
+    default_value_t = HANDSHAKE_TIMEOUT, value_parser = clap::value_parser!(u64).range(MIN_TIMEOUT..))]
+    pub handshake_timeout: u64,
+
+    /// Timeout of a single read from the peer during a handshake, in milliseconds, at least 100
+    #[arg(long, env = "ETH_HANDSHAKE_READ_TIMEOUT", default_value_t = READ_TIMEOUT,
+    value_parser = clap::value_parser!(u64).range(MIN_TIMEOUT..))]
+    pub read_timeout: u64,
+
+    /// Deadline of the whole run, in milliseconds, at least 100; unlimited by default
+    #[arg(long, env = "ETH_HANDSHAKE_DEADLINE",
+    value_parser = clap::value_parser!(u64).range(MIN_TIMEOUT..))]
+    pub deadline: Option<u64>,
+}
+
+impl From<TimeoutArgs> for Timeouts {
+    fn from(args: TimeoutArgs) -> Self {
+        Self {
+            connect: Duration::from_millis(args.connect_timeout),
+            ..args.handshake.into()
+        }
+    }
+}
+
+/// The connect timeout is left at its default, as a listener never connects
+impl From<HandshakeTimeoutArgs> for Timeouts {
+    fn from(args: HandshakeTimeoutArgs) -> Self {
+        Self {
+            handshake: Duration::from_millis(args.handshake_timeout),
+            read: Duration::from_millis(args.read_timeout),
+            deadline: args.deadline.map(Duration::from_millis),
+            ..Self::default()
+        }
+    }
+}
+
+/// Retries of failed dials
+#[derive(Args)]
+struct RetryArgs {
+    /// Maximum number of retries of a failed dial, for failures that are likely temporary
+    #[arg(long, env = "ETH_HANDSHAKE_RETRIES", default_value_t = RETRIES)]
+    pub retries: u32,
+
+    /// Backoff before the first retry, in milliseconds, which doubles with every further retry
+    #[arg(long, env = "ETH_HANDSHAKE_BACKOFF_BASE", default_value_t = BACKOFF_BASE)]
+    pub backoff_base: u64,
+
+    /// Upper bound of the backoff between retries, in milliseconds
+    #[arg(long, env = "ETH_HANDSHAKE_BACKOFF_MAX", default_value_t = BACKOFF_MAX)]
+    pub backoff_max: u64,
+
+    /// Randomize every backoff, between a half of it and all of it
+    #[arg(long, env = "ETH_HANDSHAKE_JITTER")]
+    pub jitter: bool,
+}
+
+impl From<RetryArgs> for RetryPolicy {
+    fn from(args: RetryArgs) -> Self {
+        Self {
+            retries: args.retries,
+            backoff_base: Duration::from_millis(args.backoff_base),
+            backoff_max: Duration::from_millis(args.backoff_max),
+            jitter: args.jitter,
+        }
+    }
+}
+
+/// Resolution of the recipients' host names
+#[derive(Args)]
+struct ResolverArgs {
+    /// Path to a file in the `/etc/hosts` format, which maps the recipients' host names
+    /// to IP addresses, instead of the system resolver
+    #[arg(long, env = "ETH_HANDSHAKE_HOSTS_FILE")]
+    pub hosts_file: Option<PathBuf>,
+}
+
+/// Our node's identity
+#[derive(Args)]
+struct IdentityArgs {
+    /// Path to our node key file in the geth format, i.e., 64 hex characters;
+    /// a new key is generated and saved there if the file doesn't exist.
+    /// Without a node key, our node ID is random on every run.
+    #[arg(long, env = "ETH_HANDSHAKE_NODEKEY",
+    conflicts_with_all = ["nodekey_hex", "keystore"])]
+    pub nodekey: Option<PathBuf>,
+
+    /// Our node key in the geth format, i.e., 64 hex characters
+    #[arg(long, env = "ETH_HANDSHAKE_NODEKEY_HEX", conflicts_with = "keystore")]
+    pub nodekey_hex: Option<String>,
+
+    /// Path to an encrypted keystore file in the Web3 Secret Storage format,
+    /// which holds our node key; requires a passphrase file or environment variable
+    #[arg(long, env = "ETH_HANDSHAKE_KEYSTORE", requires = "passphrase")]
+    pub keystore: Option<PathBuf>,
+
+    /// Path to a file whose first line is the keystore's passphrase
+    #[arg(
+        long,
+        env = "ETH_HANDSHAKE_PASSPHRASE_FILE",
+        group = "passphrase",
+        requires = "keystore"
+    )]
+    pub passphrase_file: Option<PathBuf>,
+
+    /// Name of an environment variable that holds the keystore's passphrase
+    #[arg(
+        long,
+        env = "ETH_HANDSHAKE_PASSPHRASE_ENV",
+        group = "passphrase",
+        requires = "keystore"
+    )]
+    pub passphrase_env: Option<String>,
+}
+
+impl From<IdentityArgs> for KeySource {
+    fn from(args: IdentityArgs) -> Self {
+        let passphrase = match (args.passphrase_file, args.passphrase_env) {
+            (Some(path), _) => Some(PassphraseSource::File(path)),
+            (None, Some(name)) => Some(PassphraseSource::Env(name)),
+            (None, None) => None,
+        };
+
+        match (args.nodekey, args.nodekey_hex, args.keystore, passphrase) {
+            (Some(path), _, _, _) => Self::NodeKeyFile(path),
+            (None, Some(hex), _, _) => Self::NodeKeyHex(SecretString::new(hex)),
+            (None, None, Some(path), Some(passphrase)) => Self::Keystore(path, passphrase),
+            _ => Self::Random,
+        }
+    }
+}
+
+/// Machine-readable output
+#[derive(Args)]
+struct OutputArgs {
+    /// Write a machine-readable record per dialed recipient, in the given format
+    #[arg(short, long, env = "ETH_HANDSHAKE_OUTPUT", value_enum)]
+    pub output: Option<OutputFormat>,
+
+    /// Path to a file to write the machine-readable records to,
+    /// instead of the standard output
+    #[arg(long, env = "ETH_HANDSHAKE_OUTPUT_FILE", requires = "output")]
+    pub output_file: Option<PathBuf>,
+}
+
+/// Fills in the arguments that are neither on the command line nor in the environment
+/// from the configuration file
+trait Merge {
+    fn merge(self, matches: &ArgMatches, config: &Config) -> Self;
+}
+
+/// Whether the argument is on the command line or in the environment
+fn is_explicit(matches: &ArgMatches, id: &str) -> bool {
+    matches!(
+        matches.value_source(id),
+        Some(ValueSource::CommandLine | ValueSource::EnvVariable)
+    )
+}
+
+/// Takes the value from the configuration file, if it's there and the argument isn't explicit
+fn merge_value<T>(matches: &ArgMatches, id: &str, value: T, file_value: Option<T>) -> T {
+    match file_value {
+        Some(file_value) if !is_explicit(matches, id) => file_value,
+        _ => value,
+    }
+}
+
+impl Merge for CliCommand {
+    fn merge(self, matches: &ArgMatches, config: &Config) -> Self {
+        match self {
+            Self::Dial(args) => Self::Dial(args.merge(matches, config)),
+            Self::Listen(args) => Self::Listen(args.merge(matches, config)),
+            Self::Serve(args) => Self::Serve(args.merge(matches, config)),
+            Self::Keygen(args) => Self::Keygen(args.merge(matches, config)),
+            Self::Whoami(args) => Self::Whoami(args.merge(matches, config)),
+            Self::Inspect(args) => Self::Inspect(args.merge(matches, config)),
+            Self::Crawl(args) => Self::Crawl(args.merge(matches, config)),
+        }
+    }
+}
+
+impl Merge for DialArgs {
+    fn merge(self, matches: &ArgMatches, config: &Config) -> Self {
+        Self {
+            dialer: self.dialer.merge(matches, config),
+            output: self.output.merge(matches, config),
+        }
+    }
+}
+
+impl Merge for ListenArgs {
+    fn merge(self, matches: &ArgMatches, config: &Config) -> Self {
+        Self {
+            listen_address: merge_value(
+                matches,
+                "listen_address",
+                self.listen_address,
+                config.listen_address,
+            ),
+            max_inbound: merge_value(matches, "max_inbound", self.max_inbound, config.max_inbound),
+            timeouts: self.timeouts.merge(matches, config),
+            identity: self.identity.merge(matches, config),
+        }
+    }
+}
+
+impl Merge for ServeArgs {
+    fn merge(self, matches: &ArgMatches, config: &Config) -> Self {
+        Self {
+            listen_address: merge_value(
+                matches,
+                "listen_address",
+                self.listen_address,
+                config.listen_address,
+            ),
+            max_inbound: merge_value(matches, "max_inbound", self.max_inbound, config.max_inbound),
+            dialer: self.dialer.merge(matches, config),
+            output: self.output.merge(matches, config),
+        }
+    }
+}
+
+impl Merge for NodeArgs {
+    fn merge(self, matches: &ArgMatches, config: &Config) -> Self {
+        Self {
+            listen_address: merge_value(
+                matches,
+                "listen_address",
+                self.listen_address,
+                config.listen_address,
+            ),
+            identity: self.identity.merge(matches, config),
+        }
+    }
+}
+
+impl Merge for InspectArgs {
+    fn merge(self, matches: &ArgMatches, config: &Config) -> Self {
+        Self {
+            enode: self.enode,
+            timeouts: self.timeouts.merge(matches, config),
+            retry: self.retry.merge(matches, config),
+            resolver: self.resolver.merge(matches, config),
+            identity: self.identity.merge(matches, config),
+        }
+    }
+}
+
+impl Merge for DialerArgs {
+    fn merge(self, matches: &ArgMatches, config: &Config) -> Self {
+        Self {
+            recipients: self.recipients.merge(matches, config),
+            concurrency: merge_value(matches, "concurrency", self.concurrency, config.concurrency),
+            fail_threshold: merge_value(
+                matches,
+                "fail_threshold",
+                self.fail_threshold,
+                config.fail_threshold.map(Some),
+            ),
+            timeouts: self.timeouts.merge(matches, config),
+            retry: self.retry.merge(matches, config),
+            resolver: self.resolver.merge(matches, config),
+            identity: self.identity.merge(matches, config),
+        }
+    }
+}
+
+/// The recipients are taken as a whole, either from the command line and the environment,
+/// or from the configuration file, so that they are never mixed up
+impl Merge for RecipientArgs {
+    fn merge(self, matches: &ArgMatches, config: &Config) -> Self {
+        if is_explicit(matches, "recipient_enodes") || is_explicit(matches, "file_path") {
+            return self;
+        }
+
+        Self {
+            recipient_enodes: config.recipient_enodes.clone(),
+            file_path: config.file_path.clone(),
+        }
+    }
+}
+
+impl Merge for TimeoutArgs {
+    fn merge(self, matches: &ArgMatches, config: &Config) -> Self {
+        Self {
+            connect_timeout: merge_value(
+                matches,
+                "connect_timeout",
+                self.connect_timeout,
+                config.connect_timeout,
+            ),
+            handshake: self.handshake.merge(matches, config),
+        }
+    }
+}
+
+impl Merge for HandshakeTimeoutArgs {
+    fn merge(self, matches: &ArgMatches, config: &Config) -> Self {
+        Self {
+            handshake_timeout: merge_value(
+                matches,
+                "handshake_timeout",
+                self.handshake_timeout,
+                config.handshake_timeout,
+            ),
+            read_timeout: merge_value(
+                matches,
+                "read_timeout",
+                self.read_timeout,
+                config.read_timeout,
+            ),
+            deadline: merge_value(
+                matches,
+                "deadline",
+                self.deadline,
+                config.deadline.map(Some),
+            ),
+        }
+    }
+}
+
+impl Merge for RetryArgs {
+    fn merge(self, matches: &ArgMatches, config: &Config) -> Self {
+        Self {
+            retries: merge_value(matches, "retries", self.retries, config.retries),
+            backoff_base: merge_value(
+                matches,
+                "backoff_base",
+                self.backoff_base,
+                config.backoff_base,
+            ),
+            backoff_max: merge_value(matches, "backoff_max", self.backoff_max, config.backoff_max),
+            jitter: merge_value(matches, "jitter", self.jitter, config.jitter),
+        }
+    }
+}
+
+impl Merge for ResolverArgs {
+    fn merge(self, matches: &ArgMatches, config: &Config) -> Self {
+        Self {
+            hosts_file: merge_value(
+                matches,
+                "hosts_file",
+                self.hosts_file,
+                config.hosts_file.clone().map(Some),
+            ),
+        }
+    }
+}
+
+/// Our node's identity is taken as a whole, either from the command line and the environment,
+/// or from the configuration file, so that its sources never conflict
+impl Merge for IdentityArgs {
+    fn merge(self, matches: &ArgMatches, config: &Config) -> Self {
+        let ids = [
+            "nodekey",
+            "nodekey_hex",
+            "keystore",
+            "passphrase_file",
+            "passphrase_env",
+        ];
+        if ids.iter().any(|id| is_explicit(matches, id)) {
+            return self;
+        }
+
+        Self {
+            nodekey: config.nodekey.clone(),
+            nodekey_hex: None,
+            keystore: config.keystore.clone(),
+            passphrase_file: config.passphrase_file.clone(),
+            passphrase_env: config.passphrase_env.clone(),
+        }
+    }
+}
+
+/// The output is taken as a whole, as the output file requires the output format
+impl Merge for OutputArgs {
+    fn merge(self, matches: &ArgMatches, config: &Config) -> Self {
+        if is_explicit(matches, "output") {
+            return self;
+        }
+
+        Self {
+            output: config.output,
+            output_file: config.output_file.clone(),
+        }
+    }
+}
+
+/// The mode of operation, which is picked with a subcommand
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Command {
+    Dial,
+    Listen,
+    Serve,
+    Keygen,
+    Whoami,
+    Inspect,
+    Crawl,
+}
+
 /// Parsed CLI arguments
-/// - timeout
+/// - subcommand
+/// - timeouts
 /// - list of enodes obtained from command line
 /// - optional path to a text file with a list of enodes
+/// - maximum number of nodes to dial at the same time
+/// - retry policy
+/// - optional path to a hosts file for resolving the recipients' host names
+/// - source of our node key
+/// - address to listen on, which is also the address in our enode
+/// - maximum number of inbound handshakes to answer at the same time
+/// - optional machine-readable output format
+/// - optional path to a file for the machine-readable output
+/// - optional percentage of failed handshakes that is tolerated
+/// - optional log filter
+///
+/// Arguments that the subcommand doesn't take have their default values.
 #[derive(Debug)]
 pub struct ParsedArgs {
-    pub timeout: u64,
+    pub command: Command,
+    pub timeouts: Timeouts,
     pub cli_enodes: Vec<Enode>,
     pub file_path: Option<PathBuf>,
+    pub concurrency: usize,
+    pub retry_policy: RetryPolicy,
+    pub hosts_file: Option<PathBuf>,
+    pub key_source: KeySource,
+    pub listen_address: SocketAddr,
+    pub max_inbound: usize,
+    pub output: Option<OutputFormat>,
+    pub output_file: Option<PathBuf>,
+    pub fail_threshold: Option<u8>,
+    pub log_level: Option<String>,
+}
+
+impl ParsedArgs {
+    /// Arguments of the given subcommand, which are all set to their default values
+    fn new(command: Command) -> Self {
+        Self {
+            command,
+            timeouts: Timeouts::default(),
+            cli_enodes: Vec::new(),
+            file_path: None,
+            concurrency: CONCURRENCY,
+            retry_policy: RetryPolicy::default(),
+            hosts_file: None,
+            key_source: KeySource::default(),
+            listen_address: LISTEN_ADDRESS
+                .parse()
+                .expect("The default listen address is valid"),
+            max_inbound: MAX_INBOUND,
+            output: None,
+            output_file: None,
+            fail_threshold: None,
+            log_level: None,
+        }
+    }
+
+    /// Sets the arguments that the subcommands which dial a list of recipient enodes share
+    fn with_dialer(self, dialer: DialerArgs) -> Self {
+        Self {
+            timeouts: dialer.timeouts.into(),
+            cli_enodes: parse_cli_enodes(dialer.recipients.recipient_enodes),
+            file_path: dialer.recipients.file_path,
+            concurrency: dialer.concurrency as usize,
+            retry_policy: dialer.retry.into(),
+            hosts_file: dialer.resolver.hosts_file,
+            key_source: dialer.identity.into(),
+            fail_threshold: dialer.fail_threshold,
+            ..self
+        }
+    }
+
+    /// Sets the machine-readable output arguments
+    fn with_output(self, output: OutputArgs) -> Self {
+        Self {
+            output: output.output,
+            output_file: output.output_file,
+            ..self
+        }
+    }
+
+    /// Sets the arguments of the subcommands that only work with our node's identity
+    fn with_node(self, node: NodeArgs) -> Self {
+        Self {
+            key_source: node.identity.into(),
+            listen_address: node.listen_address,
+            ..self
+        }
+    }
+}
+
+impl From<CliArgs> for ParsedArgs {
+    fn from(args: CliArgs) -> Self {
+        let parsed_args = match args.command {
+            CliCommand::Dial(args) => Self::new(Command::Dial)
+                .with_dialer(args.dialer)
+                .with_output(args.output),
+            CliCommand::Listen(args) => Self {
+                timeouts: args.timeouts.into(),
+                key_source: args.identity.into(),
+                listen_address: args.listen_address,
+                max_inbound: args.max_inbound as usize,
+                ..Self::new(Command::Listen)
+            },
+            CliCommand::Serve(args) => Self {
+                listen_address: args.listen_address,
+                max_inbound: args.max_inbound as usize,
+                ..Self::new(Command::Serve)
+            }
+            .with_dialer(args.dialer)
+            .with_output(args.output),
+            CliCommand::Keygen(args) => Self::new(Command::Keygen).with_node(args),
+            CliCommand::Whoami(args) => Self::new(Command::Whoami).with_node(args),
+            CliCommand::Inspect(args) => Self {
+                timeouts: args.timeouts.into(),
+                cli_enodes: parse_cli_enodes(Some(vec![args.enode])),
+                concurrency: 1,
+                retry_policy: args.retry.into(),
+                hosts_file: args.resolver.hosts_file,
+                key_source: args.identity.into(),
+                ..Self::new(Command::Inspect)
+            },
+            CliCommand::Crawl(args) => Self::new(Command::Crawl).with_dialer(args),
+        };
+
+        Self {
+            log_level: args.log_level,
+            ..parsed_args
+        }
+    }
 }
 
-/// Parse CLI arguments
+/// Parse CLI arguments, the environment, and the configuration file, if one is given
+///
+/// Exits with a usage error if they are invalid.
 ///
 /// # Returns
 /// [`ParsedArgs`]
 pub fn parse_cli_args() -> ParsedArgs {
-    let args = CliArgs::parse();
+    parse_args(std::env::args_os()).unwrap_or_else(|err| err.exit())
+}
+
+/// Parse the given CLI arguments, the environment, and the configuration file, if one is given
+///
+/// # Errors
+/// - [`clap::Error`], if the arguments are invalid, the configuration file can't be loaded,
+///   or no recipients are provided to a subcommand that dials them
+fn parse_args(
+    args: impl IntoIterator<Item = impl Into<OsString> + Clone>,
+) -> Result<ParsedArgs, clap::Error> {
+    let mut command = CliArgs::command();
+    let matches = command.try_get_matches_from_mut(args)?;
+    let args = CliArgs::from_arg_matches(&matches)?;
 
-    let timeout = args.timeout;
-    let recipient_enodes = args.recipient_enodes;
-    let file_path = args.file_path;
+    let config = match &args.config {
+        Some(path) => load_config(path)
+            .map_err(|err| command.error(ErrorKind::InvalidValue, err.to_string()))?,
+        None => Config::default(),
+    };
+    let (_, subcommand_matches) = matches
+        .subcommand()
+        .expect("A subcommand is required by the parser");
 
-    let cli_enodes = parse_cli_enodes(recipient_enodes);
+    let args = CliArgs {
+        command: args.command.merge(subcommand_matches, &config),
+        log_level: merge_value(
+            &matches,
+            "log_level",
+            args.log_level,
+            config.log_level.clone().map(Some),
+        ),
+        ..args
+    };
 
-    ParsedArgs {
-        timeout,
-        cli_enodes,
-        file_path,
+    let dialer = match &args.command {
+        CliCommand::Dial(DialArgs { dialer, .. })
+        | CliCommand::Serve(ServeArgs { dialer, .. })
+        | CliCommand::Crawl(dialer) => Some(dialer),
+        _ => None,
+    };
+    if dialer.is_some_and(|DialerArgs { recipients, .. }| {
+        recipients.recipient_enodes.is_none() && recipients.file_path.is_none()
+    }) {
+        return Err(command.error(
+            ErrorKind::MissingRequiredArgument,
+            "Recipient enodes are required: --recipient-enodes or --file-path",
+        ));
+    }
+    if dialer.is_some_and(|DialerArgs { retry, .. }| retry.backoff_base > retry.backoff_max) {
+        return Err(command.error(
+            ErrorKind::ValueValidation,
+            "--backoff-base can't be greater than --backoff-max",
+        ));
     }
+
+    Ok(args.into())
 }
 
 /// Parse a list of enodes from command line
@@ -82,10 +841,21 @@ fn parse_cli_enodes(recipient_enodes: Option<Vec<String>>) -> Vec<Enode> {
 
 #[cfg(test)]
 mod tests {
-    use crate::constants::{TEST_ENODE, TEST_HOSTNAME, TEST_USERNAME};
+    use crate::constants::{
+        TEST_CONFIG, TEST_ENODE, TEST_FILE, TEST_HOSTNAME, TEST_HOSTS_FILE, TEST_USERNAME,
+    };
+
+    use crate::test_utils::{lock_env, with_env};
 
     use super::*;
 
+    /// Parses the arguments with the environment locked, as the parser reads it
+    fn parse(args: &[&str]) -> Result<ParsedArgs, clap::Error> {
+        let _env = lock_env();
+
+        parse_args(["ethereum-handshake"].iter().chain(args))
+    }
+
     #[test]
     fn test_parse_cli_enodes_none() {
         let recipient_enodes = None;
@@ -97,7 +867,7 @@ mod tests {
 
     #[test]
     fn test_parse_cli_enodes_some_pass() {
-        let mut recipient_enodes = vec![TEST_ENODE, TEST_ENODE, TEST_ENODE]
+        let mut recipient_enodes = [TEST_ENODE, TEST_ENODE, TEST_ENODE]
             .iter()
             .map(|enode| enode.to_string())
             .collect::<Vec<String>>();
@@ -109,10 +879,10 @@ mod tests {
 
         assert_eq!(enodes_len - 1, result.len());
 
-        assert_eq!(TEST_USERNAME, result[0].username);
-        assert_eq!(TEST_HOSTNAME, result[0].hostname);
-        assert_eq!(TEST_USERNAME, result[1].username);
-        assert_eq!(TEST_HOSTNAME, result[1].hostname);
+        assert_eq!(TEST_USERNAME, result[0].node_id.to_string());
+        assert_eq!(TEST_HOSTNAME, result[0].address.to_string());
+        assert_eq!(TEST_USERNAME, result[1].node_id.to_string());
+        assert_eq!(TEST_HOSTNAME, result[1].address.to_string());
     }
 
     #[test]
@@ -126,4 +896,140 @@ mod tests {
 
         assert!(result.is_empty());
     }
+
+    #[test]
+    fn test_parse_dial() {
+        let args = parse(&["dial", "-r", TEST_ENODE, "--retries", "2", "-o", "csv"]).unwrap();
+
+        assert_eq!(Command::Dial, args.command);
+        assert_eq!(1, args.cli_enodes.len());
+        assert_eq!(2, args.retry_policy.retries);
+        assert_eq!(Some(OutputFormat::Csv), args.output);
+    }
+
+    #[test]
+    fn test_parse_dial_without_recipients() {
+        assert!(parse(&["dial"]).is_err());
+        assert!(parse(&["crawl", "-c", "4"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_listen() {
+        let args = parse(&["listen", "-l", "127.0.0.1:30304"]).unwrap();
+
+        assert_eq!(Command::Listen, args.command);
+        assert_eq!(30304, args.listen_address.port());
+        assert_eq!(MAX_INBOUND, args.max_inbound);
+        assert!(args.cli_enodes.is_empty());
+
+        let args = parse(&["listen", "--max-inbound", "8"]).unwrap();
+        assert_eq!(8, args.max_inbound);
+        assert!(parse(&["listen", "--max-inbound", "0"]).is_err());
+
+        let args = parse(&["listen", "-l", "[::1]:30304"]).unwrap();
+        assert!(args.listen_address.is_ipv6());
+
+        let args = parse(&["listen", "-t", "3000"]).unwrap();
+        assert_eq!(Duration::from_millis(3000), args.timeouts.handshake);
+
+        // listening doesn't take recipients, nor a connect timeout, as it never connects
+        assert!(parse(&["listen", "-r", TEST_ENODE]).is_err());
+        let err = parse(&["listen", "--connect-timeout", "500"]).unwrap_err();
+        assert_eq!(ErrorKind::UnknownArgument, err.kind());
+    }
+
+    #[test]
+    fn test_parse_inspect() {
+        let args = parse(&["inspect", TEST_ENODE]).unwrap();
+
+        assert_eq!(Command::Inspect, args.command);
+        assert_eq!(TEST_HOSTNAME, args.cli_enodes[0].address.to_string());
+        assert_eq!(None, args.hosts_file);
+        assert!(parse(&["inspect"]).is_err());
+
+        let args = parse(&["inspect", "--hosts-file", TEST_HOSTS_FILE, TEST_ENODE]).unwrap();
+        assert_eq!(Some(PathBuf::from(TEST_HOSTS_FILE)), args.hosts_file);
+    }
+
+    #[test]
+    fn test_parse_without_subcommand() {
+        assert!(parse(&[]).is_err());
+        assert!(parse(&["-r", TEST_ENODE]).is_err());
+    }
+
+    #[test]
+    fn test_parse_config() {
+        let args = parse(&["dial", "--config", TEST_CONFIG]).unwrap();
+
+        assert_eq!(Some(PathBuf::from(TEST_FILE)), args.file_path);
+        assert_eq!(Duration::from_millis(2500), args.timeouts.handshake);
+        assert_eq!(Duration::from_millis(READ_TIMEOUT), args.timeouts.read);
+        assert_eq!(4, args.concurrency);
+        assert_eq!(Some(OutputFormat::Ndjson), args.output);
+        assert_eq!(Some("debug".to_string()), args.log_level);
+    }
+
+    #[test]
+    fn test_parse_config_overridden() {
+        let args = with_env("ETH_HANDSHAKE_BACKOFF_MAX", "7000", || {
+            parse_args([
+                "ethereum-handshake",
+                "dial",
+                "--config",
+                TEST_CONFIG,
+                "-r",
+                TEST_ENODE,
+                "-t",
+                "3000",
+                "--log-level",
+                "warn",
+            ])
+        })
+        .unwrap();
+
+        // the recipients on the command line replace the ones in the file
+        assert_eq!(1, args.cli_enodes.len());
+        assert_eq!(None, args.file_path);
+        assert_eq!(Duration::from_millis(3000), args.timeouts.handshake);
+        assert_eq!(Duration::from_millis(7000), args.retry_policy.backoff_max);
+        assert_eq!(4, args.concurrency);
+        assert_eq!(Some("warn".to_string()), args.log_level);
+    }
+
+    #[test]
+    fn test_parse_backoff_base_above_max() {
+        let err = parse(&[
+            "dial",
+            "-r",
+            TEST_ENODE,
+            "--backoff-base",
+            "2000",
+            "--backoff-max",
+            "1000",
+        ])
+        .unwrap_err();
+        assert_eq!(ErrorKind::ValueValidation, err.kind());
+
+        // the default maximum applies, if only the base is given
+        let err = parse(&["dial", "-r", TEST_ENODE, "--backoff-base", "20000"]).unwrap_err();
+        assert_eq!(ErrorKind::ValueValidation, err.kind());
+
+        assert!(parse(&[
+            "dial",
+            "-r",
+            TEST_ENODE,
+            "--backoff-base",
+            "1000",
+            "--backoff-max",
+            "1000"
+        ])
+        .is_ok());
+    }
+
+    #[test]
+    fn test_parse_config_invalid() {
+        let err = parse(&["dial", "--config", "tests/test_nonexistent.toml"]).unwrap_err();
+
+        assert_eq!(ErrorKind::InvalidValue, err.kind());
+    }
 }
@@ -0,0 +1,236 @@
+//! The TOML configuration file
+//!
+//! The file holds the run options under the same names as the long CLI options,
+//! e.g., `handshake-timeout = 2500`. Every option is optional.
+//!
+//! CLI options and `ETH_HANDSHAKE_*` environment variables take precedence over the file.
+//! Relative paths in the file are relative to the file's directory, so that the file
+//! can be kept next to the files that it refers to, such as a list of enodes.
+
+use std::fs;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::constants::MIN_TIMEOUT;
+use crate::errors::ConfigError;
+use crate::output::OutputFormat;
+
+/// The contents of a configuration file
+#[derive(Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Config {
+    pub connect_timeout: Option<u64>,
+    pub handshake_timeout: Option<u64>,
+    pub read_timeout: Option<u64>,
+    pub deadline: Option<u64>,
+    pub recipient_enodes: Option<Vec<String>>,
+    pub file_path: Option<PathBuf>,
+    pub concurrency: Option<u16>,
+    pub fail_threshold: Option<u8>,
+    pub retries: Option<u32>,
+    pub backoff_base: Option<u64>,
+    pub backoff_max: Option<u64>,
+    pub jitter: Option<bool>,
+    pub hosts_file: Option<PathBuf>,
+    pub nodekey: Option<PathBuf>,
+    pub keystore: Option<PathBuf>,
+    pub passphrase_file: Option<PathBuf>,
+    pub passphrase_env: Option<String>,
+    pub listen_address: Option<SocketAddr>,
+    pub max_inbound: Option<u16>,
+    pub output: Option<OutputFormat>,
+    pub output_file: Option<PathBuf>,
+    pub log_level: Option<String>,
+}
+
+impl Config {
+    /// Checks the values the way the CLI options are checked
+    ///
+    /// # Errors
+    /// - [`ConfigError::InvalidValue`], if a value is out of its range,
+    ///   or if options that go together are missing or conflict
+    fn validate(&self) -> Result<(), ConfigError> {
+        let timeouts = [
+            ("connect-timeout", self.connect_timeout),
+            ("handshake-timeout", self.handshake_timeout),
+            ("read-timeout", self.read_timeout),
+            ("deadline", self.deadline),
+        ];
+        for (key, timeout) in timeouts {
+            if timeout.is_some_and(|timeout| timeout < MIN_TIMEOUT) {
+                return Err(ConfigError::InvalidValue(
+                    key,
+                    format!("must be at least {}", MIN_TIMEOUT),
+                ));
+            }
+        }
+
+        if self.concurrency == Some(0) {
+            return Err(ConfigError::InvalidValue(
+                "concurrency",
+                "must be at least 1".to_string(),
+            ));
+        }
+
+        if self.max_inbound == Some(0) {
+            return Err(ConfigError::InvalidValue(
+                "max-inbound",
+                "must be at least 1".to_string(),
+            ));
+        }
+
+        if let (Some(backoff_base), Some(backoff_max)) = (self.backoff_base, self.backoff_max) {
+            if backoff_base > backoff_max {
+                return Err(ConfigError::InvalidValue(
+                    "backoff-base",
+                    "can't be greater than `backoff-max`".to_string(),
+                ));
+            }
+        }
+
+        if self.fail_threshold.is_some_and(|threshold| threshold > 100) {
+            return Err(ConfigError::InvalidValue(
+                "fail-threshold",
+                "must be from 0 to 100".to_string(),
+            ));
+        }
+
+        if self.nodekey.is_some() && self.keystore.is_some() {
+            return Err(ConfigError::InvalidValue(
+                "nodekey",
+                "can't be used with `keystore`".to_string(),
+            ));
+        }
+
+        let passphrase = self.passphrase_file.is_some() || self.passphrase_env.is_some();
+        if self.keystore.is_some() != passphrase {
+            return Err(ConfigError::InvalidValue(
+                "keystore",
+                "requires `passphrase-file` or `passphrase-env`, and vice versa".to_string(),
+            ));
+        }
+
+        if self.output_file.is_some() && self.output.is_none() {
+            return Err(ConfigError::InvalidValue(
+                "output-file",
+                "requires `output`".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Makes the relative paths relative to the given directory
+    fn resolve_paths(&mut self, dir: &Path) {
+        let paths = [
+            &mut self.file_path,
+            &mut self.hosts_file,
+            &mut self.nodekey,
+            &mut self.keystore,
+            &mut self.passphrase_file,
+            &mut self.output_file,
+        ];
+
+        for path in paths.into_iter().flatten() {
+            if path.is_relative() {
+                *path = dir.join(&path);
+            }
+        }
+    }
+}
+
+/// Loads and validates a configuration file
+///
+/// # Errors
+/// - [`ConfigError::ReadError`], if the file can't be read
+/// - [`ConfigError::ParseError`], if the file isn't valid TOML, has unknown options,
+///   or has values of wrong types
+/// - [`ConfigError::InvalidValue`], if a value is invalid
+pub fn load_config(path: &Path) -> Result<Config, ConfigError> {
+    let contents = fs::read_to_string(path)
+        .map_err(|err| ConfigError::ReadError(path.to_path_buf(), err.to_string()))?;
+
+    let mut config: Config = toml::from_str(&contents)
+        .map_err(|err| ConfigError::ParseError(path.to_path_buf(), err.message().to_string()))?;
+
+    config.validate()?;
+    config.resolve_paths(path.parent().unwrap_or(Path::new("")));
+
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::constants::{TEST_CONFIG, TEST_FILE};
+
+    use super::*;
+
+    #[test]
+    fn test_load_config() {
+        let config = load_config(Path::new(TEST_CONFIG)).unwrap();
+
+        assert_eq!(Some(2500), config.handshake_timeout);
+        assert_eq!(Some(4), config.concurrency);
+        assert_eq!(Some(PathBuf::from(TEST_FILE)), config.file_path);
+        assert_eq!(Some(OutputFormat::Ndjson), config.output);
+        assert_eq!(Some("debug".to_string()), config.log_level);
+        assert_eq!(None, config.deadline);
+    }
+
+    #[test]
+    fn test_load_config_non_existent() {
+        assert!(matches!(
+            load_config(Path::new("tests/test_nonexistent.toml")),
+            Err(ConfigError::ReadError(_, _))
+        ));
+    }
+
+    #[test]
+    fn test_config_invalid() {
+        let parse = |contents: &str| {
+            let config: Config = toml::from_str(contents)?;
+            Ok::<_, toml::de::Error>(config.validate())
+        };
+
+        assert!(parse("unknown = 1").is_err());
+        assert!(parse("concurrency = \"many\"").is_err());
+        assert!(matches!(
+            parse("read-timeout = 10").unwrap(),
+            Err(ConfigError::InvalidValue("read-timeout", _))
+        ));
+        assert!(matches!(
+            parse("max-inbound = 0").unwrap(),
+            Err(ConfigError::InvalidValue("max-inbound", _))
+        ));
+        assert!(matches!(
+            parse("backoff-base = 2000\nbackoff-max = 1000").unwrap(),
+            Err(ConfigError::InvalidValue("backoff-base", _))
+        ));
+        assert!(matches!(
+            parse("keystore = \"keystore.json\"").unwrap(),
+            Err(ConfigError::InvalidValue("keystore", _))
+        ));
+        assert!(parse("output = \"csv\"\noutput-file = \"out.csv\"")
+            .unwrap()
+            .is_ok());
+    }
+
+    #[test]
+    fn test_config_resolve_paths() {
+        let mut config = Config {
+            file_path: Some(PathBuf::from("enodes.txt")),
+            nodekey: Some(PathBuf::from("/etc/nodekey")),
+            ..Config::default()
+        };
+
+        config.resolve_paths(Path::new("inventory"));
+
+        assert_eq!(
+            Some(PathBuf::from("inventory/enodes.txt")),
+            config.file_path
+        );
+        assert_eq!(Some(PathBuf::from("/etc/nodekey")), config.nodekey);
+    }
+}
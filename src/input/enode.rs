@@ -1,86 +1,242 @@
 //! Recipient's enode
 
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::str::FromStr;
+
+use k256::PublicKey;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::constants::{ENODE_DISCPORT, ENODE_SCHEME, HOST_LABEL_MAX_LEN, HOST_NAME_MAX_LEN};
 use crate::errors::EnodeParseError;
+use crate::identity::NodeId;
 
 /// Recipient's enode
 ///
-/// - username (part of recipient's enode - public key)
-/// - hostname (part of recipient's enode - address:port
-#[derive(Debug, PartialEq)]
+/// - node ID (part of recipient's enode - public key)
+/// - address (part of recipient's enode - IP address or host name, and TCP port)
+/// - discovery port (optional part of recipient's enode - UDP port, if it differs from the TCP port)
+///
+/// All are validated when the enode is parsed, so an [`Enode`] can always be dialed.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Enode {
-    pub username: String,
-    pub hostname: String,
+    pub node_id: NodeId,
+    pub address: EnodeAddress,
+    pub discovery_port: Option<u16>,
 }
 
-impl Enode {
-    /// Creates a new [`Enode`]
-    ///
-    /// # Errors
-    /// - [`EnodeParseError::InvalidRecipientUserName`], if it can't parse it
-    /// - [`EnodeParseError::InvalidRecipientEnode`], if `enode` doesn't contain `@`
-    pub fn new(enode: &str) -> Result<Self, EnodeParseError> {
-        let (username, hostname) = Self::parse(enode)?;
+/// Recipient's address
+///
+/// A host name is resolved only when the recipient is dialed,
+/// see [`crate::resolver::Resolver`].
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum EnodeAddress {
+    /// An IP address and a TCP port
+    Ip(SocketAddr),
+    /// A DNS host name and a TCP port
+    Dns(String, u16),
+}
 
-        Ok(Self { username, hostname })
+impl EnodeAddress {
+    /// The recipient's TCP port
+    pub fn port(&self) -> u16 {
+        match self {
+            Self::Ip(address) => address.port(),
+            Self::Dns(_, port) => *port,
+        }
     }
+}
 
-    /// Parses recipient's `enode` into `username` and `hostname`
-    ///
-    /// Provides some basic and simple validation as example.
-    ///
-    /// Expects full `enode` as input, which means it should begin
-    /// with `enode://`.
+impl From<SocketAddr> for EnodeAddress {
+    fn from(address: SocketAddr) -> Self {
+        Self::Ip(address)
+    }
+}
+
+impl fmt::Display for EnodeAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Ip(address) => write!(f, "{}", address),
+            Self::Dns(host_name, port) => write!(f, "{}:{}", host_name, port),
+        }
+    }
+}
+
+impl Enode {
+    /// Parses a recipient's `enode`
     ///
     /// The expected `enode` format is:
     ///
-    /// `enode://<node_id>@<ip_address>:<port>`
+    /// `enode://<node_id>@<host>:<port>[?discport=<udp_port>]`
     ///
-    /// This function doesn't require the IP address to necessarily be IPv4.
+    /// The node ID must be 128 hex characters, which represent a point on the secp256k1 curve,
+    /// and the port must be from 1 to 65535.
     ///
-    /// # Returns
-    /// A tuple of:
-    /// - `username`, the recipient's public key.
-    /// - `hostname`, the recipient's IP address and port, separated by `:`.
+    /// The host is an IPv4 address, an IPv6 address in square brackets, e.g.,
+    /// `[2001:db8::1]:30303`, or a DNS host name, e.g., `node1.lab.internal:30303`.
     ///
     /// # Errors
-    /// - [`EnodeParseError::InvalidRecipientUserName`], if it can't parse it
+    /// - [`EnodeParseError::InvalidScheme`], if `enode` doesn't begin with `enode://`
     /// - [`EnodeParseError::InvalidRecipientEnode`], if `enode` doesn't contain `@`
-    fn parse(recipient_enode: &str) -> Result<(String, String), EnodeParseError> {
-        let mut split_enode = recipient_enode.split('@');
+    /// - [`EnodeParseError::InvalidRecipientNodeId`], if the node ID is invalid
+    /// - [`EnodeParseError::InvalidRecipientHostName`], if the host is neither a valid
+    ///   IP address nor a valid host name, or is missing the port
+    /// - [`EnodeParseError::InvalidRecipientPort`], if the port is invalid
+    /// - [`EnodeParseError::InvalidQueryParameter`], if the query has a parameter
+    ///   other than `discport`, or is malformed
+    /// - [`EnodeParseError::InvalidDiscoveryPort`], if the discovery port is invalid
+    pub fn new(enode: &str) -> Result<Self, EnodeParseError> {
+        let rest = enode
+            .strip_prefix(ENODE_SCHEME)
+            .ok_or_else(|| EnodeParseError::InvalidScheme(enode.to_string()))?;
 
-        let user = split_enode.next().unwrap_or_default();
-        let username = match user.get(8..) {
-            Some(key) => key.to_string(),
-            None => return Err(EnodeParseError::InvalidRecipientUserName(user.to_string())),
-        };
+        let (node_id, rest) = rest
+            .split_once('@')
+            .ok_or(EnodeParseError::InvalidRecipientEnode)?;
 
-        let hostname = match split_enode.next() {
-            Some(addr) => addr.to_string(),
-            None => return Err(EnodeParseError::InvalidRecipientEnode),
+        let (address, query) = match rest.split_once('?') {
+            Some((address, query)) => (address, Some(query)),
+            None => (rest, None),
         };
 
-        Self::validate_hostname(&hostname)?;
+        let node_id = node_id.parse()?;
+        let address = Self::parse_address(address)?;
+        let discovery_port = query.map(Self::parse_query).transpose()?.flatten();
+
+        Ok(Self {
+            node_id,
+            address,
+            discovery_port,
+        })
+    }
 
-        Ok((username, hostname))
+    /// The recipient's public key
+    pub fn public_key(&self) -> PublicKey {
+        self.node_id.public_key()
     }
 
-    /// Validates hostname
+    /// The recipient's UDP port for node discovery, which is the TCP port,
+    /// unless the enode sets `discport`
+    pub fn udp_port(&self) -> u16 {
+        self.discovery_port.unwrap_or_else(|| self.address.port())
+    }
+
+    /// Parses the query, whose only known parameter is `discport`
     ///
-    /// A simple example
-    fn validate_hostname(hostname: &String) -> Result<(), EnodeParseError> {
-        if !hostname.contains(':') {
-            Err(EnodeParseError::InvalidRecipientHostName(
-                hostname.to_string(),
-            ))
-        } else {
-            Ok(())
+    /// # Returns
+    /// The discovery port, if the query sets it
+    fn parse_query(query: &str) -> Result<Option<u16>, EnodeParseError> {
+        let mut discovery_port = None;
+
+        for parameter in query.split('&') {
+            match parameter.split_once('=') {
+                Some((ENODE_DISCPORT, port)) if discovery_port.is_none() => {
+                    let port = port
+                        .parse::<u16>()
+                        .ok()
+                        .filter(|port| *port != 0)
+                        .ok_or_else(|| EnodeParseError::InvalidDiscoveryPort(port.to_string()))?;
+                    discovery_port = Some(port);
+                }
+                // unknown, repeated, or without a value
+                _ => {
+                    return Err(EnodeParseError::InvalidQueryParameter(
+                        parameter.to_string(),
+                    ))
+                }
+            }
+        }
+
+        Ok(discovery_port)
+    }
+
+    /// Parses the recipient's host and port, separated by `:`
+    ///
+    /// An IPv6 address has to be in square brackets, as it contains colons itself.
+    fn parse_address(address: &str) -> Result<EnodeAddress, EnodeParseError> {
+        let (host, port) = address
+            .rsplit_once(':')
+            .ok_or_else(|| EnodeParseError::InvalidRecipientHostName(address.to_string()))?;
+
+        let port = port
+            .parse::<u16>()
+            .ok()
+            .filter(|port| *port != 0)
+            .ok_or_else(|| EnodeParseError::InvalidRecipientPort(port.to_string()))?;
+
+        let ip = match host.strip_prefix('[').and_then(|ip| ip.strip_suffix(']')) {
+            Some(ipv6) => ipv6.parse::<Ipv6Addr>().map(IpAddr::V6).ok(),
+            None => host.parse::<Ipv4Addr>().map(IpAddr::V4).ok(),
+        };
+
+        match ip {
+            Some(ip) => Ok(EnodeAddress::Ip(SocketAddr::new(ip, port))),
+            None if is_host_name(host) => Ok(EnodeAddress::Dns(host.to_ascii_lowercase(), port)),
+            None => Err(EnodeParseError::InvalidRecipientHostName(host.to_string())),
+        }
+    }
+}
+
+/// Whether `host` is a valid DNS host name, per RFC 1123
+///
+/// The labels are letters, digits and hyphens, and don't begin or end with a hyphen.
+/// The last label isn't all digits, so that a malformed IPv4 address isn't taken for a host name.
+fn is_host_name(host: &str) -> bool {
+    let is_label = |label: &str| {
+        (1..=HOST_LABEL_MAX_LEN).contains(&label.len())
+            && label
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b == b'-')
+            && !label.starts_with('-')
+            && !label.ends_with('-')
+    };
+
+    host.len() <= HOST_NAME_MAX_LEN
+        && host.split('.').all(is_label)
+        && host
+            .rsplit('.')
+            .next()
+            .is_some_and(|last| !last.bytes().all(|b| b.is_ascii_digit()))
+}
+
+impl FromStr for Enode {
+    type Err = EnodeParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::new(s)
+    }
+}
+
+impl fmt::Display for Enode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}@{}", ENODE_SCHEME, self.node_id, self.address)?;
+
+        if let Some(port) = self.discovery_port {
+            write!(f, "?{}={}", ENODE_DISCPORT, port)?;
         }
+
+        Ok(())
+    }
+}
+
+impl Serialize for Enode {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Enode {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let enode = String::deserialize(deserializer)?;
+
+        enode.parse().map_err(de::Error::custom)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::constants::{TEST_ENODE, TEST_HOSTNAME, TEST_USERNAME};
+    use crate::constants::{TEST_DNS_HOSTNAME, TEST_ENODE, TEST_HOSTNAME, TEST_USERNAME};
+    use crate::errors::NodeIdError;
 
     use super::*;
 
@@ -92,9 +248,117 @@ mod tests {
         assert!(result.is_ok());
 
         let result = result.unwrap();
-        let (username, hostname) = (result.username, result.hostname);
-        assert_eq!(TEST_USERNAME, username);
-        assert_eq!(TEST_HOSTNAME, hostname);
+        let (node_id, address) = (result.node_id, &result.address);
+        assert_eq!(TEST_USERNAME, node_id.to_string());
+        assert_eq!(TEST_HOSTNAME, address.to_string());
+        assert_eq!(TEST_ENODE, result.to_string());
+    }
+
+    #[test]
+    fn test_parse_pass_ipv6() {
+        let enode = format!("enode://{}@[2001:db8::1]:30303", TEST_USERNAME);
+
+        let result = Enode::new(&enode).unwrap();
+
+        assert_eq!(
+            EnodeAddress::Ip(SocketAddr::new("2001:db8::1".parse().unwrap(), 30303)),
+            result.address
+        );
+        assert_eq!(enode, result.to_string());
+    }
+
+    #[test]
+    fn test_parse_fail_bad_ipv6() {
+        // IPv6 addresses need brackets, and brackets are only for IPv6 addresses
+        for address in [
+            "2001:db8::1:30303",
+            "[178.128.136.233]:30303",
+            "[2001:db8::1:30303",
+        ] {
+            let enode = format!("enode://{}@{}", TEST_USERNAME, address);
+
+            assert!(matches!(
+                enode.parse::<Enode>(),
+                Err(EnodeParseError::InvalidRecipientHostName(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_parse_pass_host_name() {
+        let enode = format!("enode://{}@{}", TEST_USERNAME, TEST_DNS_HOSTNAME);
+
+        let result = Enode::new(&enode).unwrap();
+
+        assert_eq!(
+            EnodeAddress::Dns("node1.lab.internal".to_string(), 30303),
+            result.address
+        );
+        assert_eq!(30303, result.address.port());
+        assert_eq!(enode, result.to_string());
+
+        // host names are case-insensitive
+        let enode = format!("enode://{}@Node1.LAB.internal:30303", TEST_USERNAME);
+        assert_eq!(result, enode.parse().unwrap());
+    }
+
+    #[test]
+    fn test_parse_fail_bad_host_name() {
+        for host in ["-node1.lab", "node1..lab", "node_1.lab", "999.1.1.1", ""] {
+            let enode = format!("enode://{}@{}:30303", TEST_USERNAME, host);
+
+            assert_eq!(
+                Err(EnodeParseError::InvalidRecipientHostName(host.to_string())),
+                enode.parse::<Enode>()
+            );
+        }
+
+        let host = format!("{}.lab", "a".repeat(HOST_LABEL_MAX_LEN + 1));
+        assert!(!is_host_name(&host));
+    }
+
+    #[test]
+    fn test_parse_pass_discport() {
+        let enode = format!("{}?discport=30301", TEST_ENODE);
+
+        let result = Enode::new(&enode).unwrap();
+
+        assert_eq!(TEST_HOSTNAME, result.address.to_string());
+        assert_eq!(Some(30301), result.discovery_port);
+        assert_eq!(30301, result.udp_port());
+        assert_eq!(enode, result.to_string());
+
+        let result = Enode::new(TEST_ENODE).unwrap();
+        assert_eq!(None, result.discovery_port);
+        assert_eq!(30303, result.udp_port());
+    }
+
+    #[test]
+    fn test_parse_fail_bad_query() {
+        for query in [
+            "",
+            "discport",
+            "discport=1&discport=2",
+            "discport=1&",
+            "foo=bar",
+        ] {
+            let enode = format!("{}?{}", TEST_ENODE, query);
+            let parameter = query.rsplit('&').next().unwrap().to_string();
+
+            assert_eq!(
+                Err(EnodeParseError::InvalidQueryParameter(parameter)),
+                enode.parse::<Enode>()
+            );
+        }
+
+        for port in ["", "0", "65536", "udp"] {
+            let enode = format!("{}?discport={}", TEST_ENODE, port);
+
+            assert_eq!(
+                Err(EnodeParseError::InvalidDiscoveryPort(port.to_string())),
+                enode.parse::<Enode>()
+            );
+        }
     }
 
     #[test]
@@ -109,17 +373,40 @@ mod tests {
     }
 
     #[test]
-    fn test_parse_fail_bad_username() {
+    fn test_parse_fail_bad_scheme() {
         let enode = "";
 
         let result = Enode::new(enode);
 
         assert!(result.is_err());
 
+        assert_eq!(Err(EnodeParseError::InvalidScheme("".to_string())), result);
+        assert!(matches!(
+            TEST_ENODE.replace("enode://", "enr://").parse::<Enode>(),
+            Err(EnodeParseError::InvalidScheme(_))
+        ));
+    }
+
+    #[test]
+    fn test_parse_fail_bad_node_id() {
+        let enode = format!("enode://{}@{}", &TEST_USERNAME[..10], TEST_HOSTNAME);
+
         assert_eq!(
-            Err(EnodeParseError::InvalidRecipientUserName("".to_string())),
-            result
+            Err(EnodeParseError::InvalidRecipientNodeId(
+                NodeIdError::InvalidHexLength(10)
+            )),
+            enode.parse::<Enode>()
         );
+
+        // 128 hex characters, but not a point on the curve
+        let enode = format!("enode://{}@{}", "0".repeat(128), TEST_HOSTNAME);
+
+        assert!(matches!(
+            enode.parse::<Enode>(),
+            Err(EnodeParseError::InvalidRecipientNodeId(
+                NodeIdError::InvalidPublicKey(_)
+            ))
+        ));
     }
 
     #[test]
@@ -138,4 +425,27 @@ mod tests {
             result
         );
     }
+
+    #[test]
+    fn test_parse_fail_bad_port() {
+        for port in ["99999", "0", "", "port"] {
+            let enode = TEST_ENODE.replace("30303", port);
+
+            assert_eq!(
+                Err(EnodeParseError::InvalidRecipientPort(port.to_string())),
+                enode.parse::<Enode>()
+            );
+        }
+    }
+
+    #[test]
+    fn test_enode_serde() {
+        let enode: Enode = TEST_ENODE.parse().unwrap();
+
+        let json = serde_json::to_string(&enode).unwrap();
+
+        assert_eq!(format!("\"{}\"", TEST_ENODE), json);
+        assert_eq!(enode, serde_json::from_str(&json).unwrap());
+        assert!(serde_json::from_str::<Enode>("\"enode://bad\"").is_err());
+    }
 }
@@ -1,16 +1,20 @@
 //! # Module [`input`]
 //!
 //! Get user input which is a recipient's enode or a list of recipient enodes
-//! that the user would like to connect to.
+//! that the user would like to connect to, and the other run options.
 //!
 //! Supported sources are:
 //! - CLI
-//! - Text file - unimplemented
+//! - Environment variables
+//! - Configuration file
+//! - Text file with a list of enodes
 
 pub use cli::*;
+pub use config::*;
 pub use enode::*;
 pub use file::*;
 
 mod cli;
+mod config;
 mod enode;
 mod file;
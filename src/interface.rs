@@ -2,103 +2,585 @@
 //!
 //! Our app can act as an initiator of a call, trying to connect to another
 //! node(s) in the peer-to-peer network, and/or it can act as a recipient of
-//! such a call (not implemented).
+//! such a call.
 //!
 //! In the former case, a user should provide the address(es) of node(s) that
 //! they'd like to call using our app as a client, through CLI.
 //!
-//! In the latter case, which is not (yet) implemented, no CLI arguments
-//! are necessary.
+//! In the latter case, no CLI arguments are necessary, but the listening
+//! address can be provided.
 
-use std::time::Duration;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use k256::SecretKey;
-use tokio::net::TcpStream;
-use tracing::{debug, error, info};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tokio::task::JoinSet;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, warn};
 
-use crate::errors::{ConnError, DialError};
-use crate::handshake::initiate_handshake;
+use crate::constants::{CONNECT_TIMEOUT, HANDSHAKE_TIMEOUT, READ_TIMEOUT};
+use crate::errors::ListenError;
+use crate::handshake::{initiate_handshake, respond_to_handshake};
+use crate::identity::NodeId;
 use crate::input::Enode;
+use crate::messages::Hello;
+use crate::report::{DialAttempt, DialEntry, DialOutcome, DialPhase, DialReport, Timings};
+use crate::resolver::Resolver;
+use crate::retry::RetryPolicy;
+
+/// Time limits for dialing and answering
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Timeouts {
+    /// Establishing a TCP connection to a recipient
+    pub connect: Duration,
+    /// A whole handshake, once the connection is established
+    pub handshake: Duration,
+    /// A single read from the peer during a handshake
+    pub read: Duration,
+    /// The whole run, measured from when dialing or listening starts; unlimited if `None`
+    pub deadline: Option<Duration>,
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Self {
+            connect: Duration::from_millis(CONNECT_TIMEOUT),
+            handshake: Duration::from_millis(HANDSHAKE_TIMEOUT),
+            read: Duration::from_millis(READ_TIMEOUT),
+            deadline: None,
+        }
+    }
+}
+
+/// How to dial recipient nodes
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DialOptions {
+    pub timeouts: Timeouts,
+    pub retry_policy: RetryPolicy,
+    /// Resolves the recipients' host names
+    pub resolver: Resolver,
+    /// Advertised in our Hello message; it should be zero
+    /// if we aren't listening for connections at the same time
+    pub listen_port: u16,
+}
 
 /// Dials all provided recipient nodes
 ///
 /// Handshaking with a node should preferably be atomic, i.e., uninterrupted,
-/// so nodes should be either dialed successively or concurrently by using
-/// multiple threads - a thread per node, or a pool of threads, i.e.,
-/// in parallel, but it could probably also be implemented safely
-/// in asynchronous concurrent manner, because `tokio` supports multithreading.
+/// so every node is dialed on its own task, where its handshake runs from
+/// start to finish. Tasks run concurrently, and possibly in parallel,
+/// because `tokio` supports multithreading.
+///
+/// At most `concurrency` nodes are dialed at the same time.
+///
+/// Failed dials are retried according to the retry policy of `options`.
+///
+/// When `shutdown` is cancelled, no new nodes are dialed, and no failed dials
+/// are retried, but the handshakes that are in progress are allowed to complete.
+/// The nodes that weren't dialed are reported as [`DialOutcome::Cancelled`].
+///
+/// When the deadline passes, no new nodes are dialed either, and the dials
+/// that are in progress are cut short. Both are reported as
+/// [`DialPhase::Deadline`] timeouts.
+///
+/// # Returns
+/// [`DialReport`], with an entry per node, in the order in which the dials finished,
+/// followed by the nodes that weren't dialed
 pub async fn dial_all(
     static_secret_key: &SecretKey,
-    timeout: u64,
+    options: DialOptions,
     enodes: Vec<Enode>,
-) -> Result<(), DialError> {
-    // TODO: Make concurrent!
-    for enode in enodes {
-        dial(static_secret_key, timeout, enode).await?;
+    concurrency: usize,
+    shutdown: CancellationToken,
+) -> DialReport {
+    let deadline = options
+        .timeouts
+        .deadline
+        .map(|deadline| Instant::now() + deadline);
+    let static_secret_key = Arc::new(static_secret_key.clone());
+    let options = Arc::new(options);
+    let semaphore = Arc::new(Semaphore::new(concurrency));
+    let mut dials = JoinSet::new();
+    let mut report = DialReport::default();
+    let mut skipped = Vec::new();
+
+    let mut enodes = enodes.into_iter();
+    while let Some(enode) = enodes.next() {
+        let permit = tokio::select! {
+            biased;
+            _ = shutdown.cancelled() => {
+                warn!("Shutting down; not dialing the remaining recipients.");
+                skipped.extend(
+                    std::iter::once(enode)
+                        .chain(enodes.by_ref())
+                        .map(|enode| undialed(enode, DialOutcome::Cancelled)),
+                );
+                break;
+            }
+            _ = until(deadline) => {
+                warn!("Deadline reached; not dialing the remaining recipients.");
+                skipped.extend(std::iter::once(enode).chain(enodes.by_ref()).map(|enode| {
+                    undialed(enode, DialOutcome::Timeout(DialPhase::Deadline))
+                }));
+                break;
+            }
+            permit = Arc::clone(&semaphore).acquire_owned() => {
+                permit.expect("The semaphore is never closed")
+            }
+        };
+
+        let static_secret_key = Arc::clone(&static_secret_key);
+        let options = Arc::clone(&options);
+        let shutdown = shutdown.clone();
+        dials.spawn(async move {
+            let entry = dial(&static_secret_key, &options, deadline, enode, shutdown).await;
+            drop(permit);
+            entry
+        });
     }
 
-    Ok(())
+    // results are collected in the order in which the dials finish
+    while let Some(joined) = dials.join_next().await {
+        match joined {
+            Ok(entry) => report.entries.push(entry),
+            Err(err) => error!("A dial task failed due to {}.", err),
+        }
+    }
+    report.entries.extend(skipped);
+
+    info!(
+        "Dialed {} recipient(s): {} succeeded, {} failed.",
+        report.entries.len(),
+        report.successes(),
+        report.failures()
+    );
+
+    report
 }
 
 /// Dial a single recipient node
 ///
-/// Tries to connect to the node and then to handshake with it.
+/// Tries to connect to the node and then to handshake with it,
+/// until the phase timeouts or the deadline run out.
+///
+/// Retries according to the retry policy of `options`, as long as the retry
+/// can start before the deadline, and `shutdown` isn't cancelled.
+///
+/// # Returns
+/// [`DialEntry`], with the outcome, the address that we connected to,
+/// the recipient's Hello message on success, timings, and every attempt
+async fn dial(
+    static_secret_key: &SecretKey,
+    options: &DialOptions,
+    deadline: Option<Instant>,
+    enode: Enode,
+    shutdown: CancellationToken,
+) -> DialEntry {
+    let mut attempts = Vec::new();
+    let mut address;
+
+    let hello = loop {
+        let start = Instant::now();
+        let mut timings = Timings::default();
+        address = None;
+
+        let result = connect_and_handshake(
+            static_secret_key,
+            options,
+            deadline,
+            &enode,
+            &mut timings,
+            &mut address,
+        )
+        .await;
+
+        timings.total = start.elapsed();
+
+        let outcome = match result {
+            Ok(hello) => {
+                attempts.push(DialAttempt {
+                    outcome: DialOutcome::Success,
+                    timings,
+                });
+                break Some(hello);
+            }
+            Err(outcome) => outcome,
+        };
+
+        let backoff = options
+            .retry_policy
+            .backoff(attempts.len() as u32 + 1, &outcome);
+        attempts.push(DialAttempt { outcome, timings });
+
+        let Some(backoff) = backoff else { break None };
+        if deadline.is_some_and(|deadline| Instant::now() + backoff >= deadline) {
+            break None;
+        }
+
+        warn!("Retrying recipient {} in {:.3?}.", enode.address, backoff);
+
+        tokio::select! {
+            _ = shutdown.cancelled() => break None,
+            _ = tokio::time::sleep(backoff) => {}
+        }
+    };
+
+    let last = attempts.last().expect("There is at least one attempt");
+
+    DialEntry {
+        enode,
+        address,
+        outcome: last.outcome.clone(),
+        hello,
+        timings: last.timings,
+        attempts,
+    }
+}
+
+/// An entry for a recipient node that wasn't dialed, because the run was interrupted,
+/// or because the deadline passed
+fn undialed(enode: Enode, outcome: DialOutcome) -> DialEntry {
+    DialEntry {
+        enode,
+        address: None,
+        outcome,
+        hello: None,
+        timings: Timings::default(),
+        attempts: Vec::new(),
+    }
+}
+
+/// Connects to a single recipient node and handshakes with it
+///
+/// Resolves the recipient's host name, if it has one, and tries to connect
+/// to every resolved address in order, until a connection is established.
+/// The resolution counts towards the connect phase, and every address has
+/// its own connect timeout.
 ///
-/// Provides some basic and simple validation as example.
+/// Records the duration of each phase that was reached in `timings`,
+/// and the address that we connected to in `address`.
 ///
-/// Expects an IPv4 address.
+/// The enode is already validated, so only the resolution, the connection
+/// and the handshake can fail.
 ///
 /// # Errors
-/// - [`DialError::ConnectionError`] wrapping [`ConnError::TcpStreamError`]
-async fn dial(static_secret_key: &SecretKey, timeout: u64, enode: Enode) -> Result<(), DialError> {
-    let username = enode.username;
-    let hostname = enode.hostname;
+/// - [`DialOutcome`] that tells why the dial failed; of the connection failures,
+///   the one of the last address
+async fn connect_and_handshake(
+    static_secret_key: &SecretKey,
+    options: &DialOptions,
+    deadline: Option<Instant>,
+    enode: &Enode,
+    timings: &mut Timings,
+    address: &mut Option<SocketAddr>,
+) -> Result<Hello, DialOutcome> {
+    let timeouts = options.timeouts;
+    let start = Instant::now();
 
-    let ip = hostname.clone();
-    let ip = ip
-        .split(':')
-        .next()
-        .expect("Improperly validated: Expected colon in hostname");
+    // resolution timeout
+    let (timeout, phase) = limit(timeouts.connect, DialPhase::Connect, deadline);
+    let resolved = tokio::time::timeout(timeout, options.resolver.resolve(&enode.address)).await;
 
-    info!("Connecting to recipient {}...", ip);
+    let candidates = match resolved {
+        Ok(Ok(candidates)) => candidates,
+        Ok(Err(err)) => {
+            timings.connect = Some(start.elapsed());
+            error!(
+                "Failed to resolve recipient {} due to {}.",
+                enode.address, err
+            );
+            return Err(DialOutcome::Unresolved(err.to_string()));
+        }
+        Err(err) => {
+            timings.connect = Some(start.elapsed());
+            error!(
+                "Failed to resolve recipient {} due to {}.",
+                enode.address, err
+            );
+            return Err(DialOutcome::Timeout(phase));
+        }
+    };
 
-    // connection timeout
-    match tokio::time::timeout(
-        Duration::from_millis(timeout),
-        TcpStream::connect(&hostname),
-    )
-    .await
-    {
-        Ok(stream) => {
-            let mut stream = match stream {
-                Ok(stream) => stream,
-                Err(err) => {
-                    return Err(DialError::from(ConnError::TcpStreamError(err.to_string())))
+    // connection timeout, per address
+    let mut stream = None;
+    let mut failure = None;
+    for candidate in candidates {
+        info!(
+            "Connecting to recipient {} at {}...",
+            enode.address, candidate
+        );
+
+        let (timeout, phase) = limit(timeouts.connect, DialPhase::Connect, deadline);
+        match tokio::time::timeout(timeout, TcpStream::connect(candidate)).await {
+            Ok(Ok(connected)) => {
+                stream = Some(connected);
+                *address = Some(candidate);
+                break;
+            }
+            Ok(Err(err)) => {
+                error!(
+                    "Failed to connect to recipient {} due to {}.",
+                    candidate, err
+                );
+                failure = Some(DialOutcome::from_connect_error(&err));
+            }
+            Err(err) => {
+                error!(
+                    "Failed to connect to recipient {} due to {}.",
+                    candidate, err
+                );
+                failure = Some(DialOutcome::Timeout(phase));
+                if phase == DialPhase::Deadline {
+                    break;
                 }
-            };
+            }
+        }
+    }
+    timings.connect = Some(start.elapsed());
 
-            info!("Connected to recipient {}.", ip);
+    let (Some(mut stream), Some(connected)) = (stream, *address) else {
+        return Err(failure.expect("The resolver returns at least one address"));
+    };
 
-            // handshake timeout
-            if let Err(err) = tokio::time::timeout(
-                Duration::from_millis(timeout),
-                initiate_handshake(static_secret_key, &mut stream, username, hostname),
-            )
-            .await
-            {
-                error!("Failed to handshake with recipient {} due to {}.", ip, err);
+    info!("Connected to recipient {}.", connected);
+
+    // handshake timeout
+    let (timeout, phase) = limit(timeouts.handshake, DialPhase::Handshake, deadline);
+    let start = Instant::now();
+    let handshaken = tokio::time::timeout(
+        timeout,
+        initiate_handshake(
+            static_secret_key,
+            &mut stream,
+            options.listen_port,
+            timeouts.read,
+            &enode.public_key(),
+            connected.to_string(),
+        ),
+    )
+    .await;
+    timings.handshake = Some(start.elapsed());
+
+    match handshaken {
+        Ok(Ok(hello)) => {
+            debug!("Recipient {} runs {}.", connected, hello.client_id);
+            Ok(hello)
+        }
+        Ok(Err(err)) => {
+            error!(
+                "Failed to handshake with recipient {} due to {}.",
+                connected, err
+            );
+            Err(DialOutcome::from(err))
+        }
+        Err(err) => {
+            error!(
+                "Failed to handshake with recipient {} due to {}.",
+                connected, err
+            );
+            Err(DialOutcome::Timeout(phase))
+        }
+    }
+}
+
+/// Bounds the timeout of a dial phase by the deadline
+///
+/// # Returns
+/// A tuple of the time limit, and the phase to report if the limit runs out,
+/// which is [`DialPhase::Deadline`] if the deadline comes first
+fn limit(timeout: Duration, phase: DialPhase, deadline: Option<Instant>) -> (Duration, DialPhase) {
+    match deadline {
+        Some(deadline) => {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining < timeout {
+                (remaining, DialPhase::Deadline)
+            } else {
+                (timeout, phase)
             }
         }
-        Err(err) => error!("Failed to connect to recipient {} due to {}.", ip, err),
+        None => (timeout, phase),
+    }
+}
+
+/// Waits until the deadline, or forever if there is none
+///
+/// Returns at once if the deadline has already passed, because the timer
+/// only fires on its next tick.
+async fn until(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) if deadline <= Instant::now() => {}
+        Some(deadline) => tokio::time::sleep_until(deadline.into()).await,
+        None => std::future::pending().await,
     }
+}
+
+/// Listens for connections from initiator nodes and answers them
+///
+/// Binds to the provided address and accepts inbound connections until
+/// `shutdown` is cancelled, or until the deadline passes.
+///
+/// At most `max_inbound` handshakes are answered at the same time.
+///
+/// # Errors
+/// - [`ListenError::BindError`], if it can't bind to the address
+pub async fn answer(
+    static_secret_key: &SecretKey,
+    listen_address: SocketAddr,
+    timeouts: Timeouts,
+    max_inbound: usize,
+    shutdown: CancellationToken,
+) -> Result<(), ListenError> {
+    let listener = bind(listen_address).await?;
+
+    serve(static_secret_key, listener, timeouts, max_inbound, shutdown).await
+}
+
+/// Binds a listener for connections from initiator nodes
+///
+/// # Errors
+/// - [`ListenError::BindError`], if it can't bind to the address
+pub async fn bind(listen_address: SocketAddr) -> Result<TcpListener, ListenError> {
+    TcpListener::bind(listen_address)
+        .await
+        .map_err(|err| ListenError::BindError(listen_address, err.to_string()))
+}
+
+/// Accepts inbound connections on a bound listener
+///
+/// Each connection is handled on its own task, so that every handshake
+/// runs uninterrupted, from start to finish.
+///
+/// At most `max_inbound` handshakes run at the same time. While that many
+/// are in progress, no new connections are accepted, so they wait in
+/// the listener's backlog.
+///
+/// When `shutdown` is cancelled, or the deadline passes, no new connections
+/// are accepted, and the handshakes that are in progress are waited for.
+///
+/// # Errors
+/// - [`ListenError::IOError`], if the listener's address can't be read
+pub async fn serve(
+    static_secret_key: &SecretKey,
+    listener: TcpListener,
+    timeouts: Timeouts,
+    max_inbound: usize,
+    shutdown: CancellationToken,
+) -> Result<(), ListenError> {
+    let deadline = timeouts.deadline.map(|deadline| Instant::now() + deadline);
+    let static_secret_key = Arc::new(static_secret_key.clone());
+    let listen_address = listener.local_addr()?;
+    let listen_port = listen_address.port();
+    let semaphore = Arc::new(Semaphore::new(max_inbound));
+    let mut handshakes = JoinSet::new();
+
+    info!(
+        "Listening on {} as node {}...",
+        listen_address,
+        NodeId::from_secret_key(&static_secret_key)
+    );
+
+    loop {
+        let (permit, accepted) = tokio::select! {
+            _ = shutdown.cancelled() => break,
+            _ = until(deadline) => {
+                info!("Deadline reached.");
+                break;
+            }
+            accepted = accept(&listener, &semaphore) => accepted,
+        };
+
+        // reap the handshakes that have already completed
+        while handshakes.try_join_next().is_some() {}
+
+        let (stream, address) = match accepted {
+            Ok(connection) => connection,
+            Err(err) => {
+                error!("Failed to accept a connection due to {}.", err);
+                continue;
+            }
+        };
+
+        info!("Accepted connection from initiator {}.", address);
+
+        let static_secret_key = Arc::clone(&static_secret_key);
+        handshakes.spawn(async move {
+            respond(static_secret_key, stream, address, listen_port, timeouts).await;
+            drop(permit);
+        });
+    }
+
+    info!(
+        "Stopped listening; waiting for {} handshake(s) to complete.",
+        handshakes.len()
+    );
+    while handshakes.join_next().await.is_some() {}
 
     Ok(())
 }
 
+/// Accepts a connection, once there is room for another handshake
+async fn accept(
+    listener: &TcpListener,
+    semaphore: &Arc<Semaphore>,
+) -> (OwnedSemaphorePermit, io::Result<(TcpStream, SocketAddr)>) {
+    let permit = Arc::clone(semaphore)
+        .acquire_owned()
+        .await
+        .expect("The semaphore is never closed");
+
+    (permit, listener.accept().await)
+}
+
 /// Answer to a single connection and handshake request
-pub async fn answer(_timeout: u64) -> Result<(), DialError> {
-    debug!("Entering `answer()`");
-    unimplemented!()
+async fn respond(
+    static_secret_key: Arc<SecretKey>,
+    mut stream: TcpStream,
+    address: SocketAddr,
+    listen_port: u16,
+    timeouts: Timeouts,
+) {
+    // handshake timeout
+    match tokio::time::timeout(
+        timeouts.handshake,
+        respond_to_handshake(&static_secret_key, &mut stream, listen_port, timeouts.read),
+    )
+    .await
+    {
+        Ok(Ok((public_key, hello))) => info!(
+            "Node {} at {} completed handshake: {}",
+            NodeId::from(&public_key).to_string(),
+            address,
+            hello
+        ),
+        Ok(Err(err)) => error!(
+            "Failed to handshake with initiator {} due to {}.",
+            address, err
+        ),
+        Err(err) => error!(
+            "Failed to handshake with initiator {} due to {}.",
+            address, err
+        ),
+    }
+}
+
+/// Creates a shutdown token that is cancelled when the process is interrupted (Ctrl+C)
+pub fn shutdown_on_ctrl_c() -> CancellationToken {
+    let shutdown = CancellationToken::new();
+
+    let token = shutdown.clone();
+    tokio::spawn(async move {
+        match tokio::signal::ctrl_c().await {
+            Ok(()) => info!("Interrupted; shutting down..."),
+            Err(err) => error!("Failed to listen for the interrupt signal due to {}.", err),
+        }
+        token.cancel();
+    });
+
+    shutdown
 }
 
 #[cfg(test)]
@@ -107,48 +589,448 @@ mod tests {
 
     use k256::SecretKey;
     use rand_core::OsRng;
+    use tokio::task::JoinHandle;
 
-    use crate::constants::{TEST_HOSTNAME, TEST_USERNAME, TIMEOUT};
-    use crate::errors::ConnError::TcpStreamError;
-    use crate::errors::DialError::ConnectionError;
+    use crate::constants::{CONCURRENCY, MAX_INBOUND, TEST_ENODE};
+    use crate::errors::HandshakeError;
+    use crate::input::EnodeAddress;
+    use crate::report::DialVerdict;
 
     use super::*;
 
     static STATIC_SK: OnceLock<SecretKey> = OnceLock::new();
 
+    /// Spawns a recipient with a random node key, which answers handshakes on the given
+    /// address until its shutdown token is cancelled
+    async fn spawn_recipient(
+        bind: &str,
+        max_inbound: usize,
+    ) -> (
+        NodeId,
+        SocketAddr,
+        CancellationToken,
+        JoinHandle<Result<(), ListenError>>,
+    ) {
+        let recipient_key = SecretKey::random(&mut OsRng);
+        let node_id = NodeId::from_secret_key(&recipient_key);
+        let listener = TcpListener::bind(bind).await.unwrap();
+        let address = listener.local_addr().unwrap();
+
+        let shutdown = CancellationToken::new();
+        let server = tokio::spawn({
+            let shutdown = shutdown.clone();
+            async move {
+                serve(
+                    &recipient_key,
+                    listener,
+                    Timeouts::default(),
+                    max_inbound,
+                    shutdown,
+                )
+                .await
+            }
+        });
+
+        (node_id, address, shutdown, server)
+    }
+
     #[tokio::test]
     async fn test_dial_pass() {
         STATIC_SK.get_or_init(|| SecretKey::random(&mut OsRng));
 
+        let (node_id, address, shutdown, server) =
+            spawn_recipient("127.0.0.1:0", MAX_INBOUND).await;
+
         let enode = Enode {
-            username: TEST_USERNAME.to_string(),
-            hostname: TEST_HOSTNAME.to_string(),
+            node_id,
+            address: address.into(),
+            discovery_port: None,
         };
 
-        assert!(dial(&STATIC_SK.get().unwrap(), TIMEOUT, enode)
+        let entry = dial(
+            STATIC_SK.get().unwrap(),
+            &DialOptions::default(),
+            None,
+            enode.clone(),
+            CancellationToken::new(),
+        )
+        .await;
+
+        assert_eq!(enode, entry.enode);
+        assert_eq!(Some(address), entry.address);
+        assert_eq!(DialOutcome::Success, entry.outcome);
+        assert_eq!(address.port(), entry.hello.unwrap().listen_port);
+        assert!(entry.timings.connect.is_some());
+        assert!(entry.timings.handshake.is_some());
+
+        shutdown.cancel();
+        assert!(server.await.unwrap().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_dial_pass_ipv6() {
+        let (node_id, address, shutdown, server) = spawn_recipient("[::1]:0", MAX_INBOUND).await;
+
+        let enode: Enode = node_id.enode(address).parse().unwrap();
+        assert_eq!(EnodeAddress::Ip(address), enode.address);
+
+        let entry = dial(
+            &SecretKey::random(&mut OsRng),
+            &DialOptions::default(),
+            None,
+            enode,
+            CancellationToken::new(),
+        )
+        .await;
+
+        assert_eq!(DialOutcome::Success, entry.outcome);
+        assert_eq!(address.port(), entry.hello.unwrap().listen_port);
+
+        shutdown.cancel();
+        assert!(server.await.unwrap().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_dial_host_name() {
+        let (node_id, address, shutdown, server) =
+            spawn_recipient("127.0.0.1:0", MAX_INBOUND).await;
+
+        // nothing listens on the first address, so the second one is used
+        let hosts = [(
+            "node1.lab.internal".to_string(),
+            vec!["127.0.0.2".parse().unwrap(), address.ip()],
+        )];
+        let options = DialOptions {
+            resolver: Resolver::Static(hosts.into_iter().collect()),
+            ..DialOptions::default()
+        };
+        let enode = |host_name: &str| Enode {
+            node_id,
+            address: EnodeAddress::Dns(host_name.to_string(), address.port()),
+            discovery_port: None,
+        };
+
+        let entry = dial(
+            &SecretKey::random(&mut OsRng),
+            &options,
+            None,
+            enode("node1.lab.internal"),
+            CancellationToken::new(),
+        )
+        .await;
+
+        assert_eq!(DialOutcome::Success, entry.outcome);
+        assert_eq!(Some(address), entry.address);
+
+        let entry = dial(
+            &SecretKey::random(&mut OsRng),
+            &options,
+            None,
+            enode("node2.lab.internal"),
+            CancellationToken::new(),
+        )
+        .await;
+
+        assert!(matches!(entry.outcome, DialOutcome::Unresolved(_)));
+        assert_eq!(None, entry.address);
+
+        shutdown.cancel();
+        assert!(server.await.unwrap().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_serve_pass() {
+        let (node_id, address, shutdown, server) =
+            spawn_recipient("127.0.0.1:0", MAX_INBOUND).await;
+        let recipient_public_key = node_id.public_key();
+
+        for _ in 0..2 {
+            let mut stream = TcpStream::connect(address).await.unwrap();
+            let hello = initiate_handshake(
+                &SecretKey::random(&mut OsRng),
+                &mut stream,
+                0,
+                Timeouts::default().read,
+                &recipient_public_key,
+                address.to_string(),
+            )
             .await
-            .is_ok());
+            .unwrap();
+
+            assert_eq!(address.port(), hello.listen_port);
+        }
+
+        shutdown.cancel();
+        assert!(server.await.unwrap().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_serve_max_inbound() {
+        let (node_id, address, shutdown, server) = spawn_recipient("127.0.0.1:0", 1).await;
+        let recipient_public_key = node_id.public_key();
+
+        // a silent initiator takes up the only handshake slot
+        let silent = TcpStream::connect(address).await.unwrap();
+
+        let handshake = |read_timeout: Duration| async move {
+            let mut stream = TcpStream::connect(address).await.unwrap();
+            initiate_handshake(
+                &SecretKey::random(&mut OsRng),
+                &mut stream,
+                0,
+                read_timeout,
+                &recipient_public_key,
+                address.to_string(),
+            )
+            .await
+        };
+
+        assert!(matches!(
+            handshake(Duration::from_millis(300)).await,
+            Err(HandshakeError::ReadTimeout)
+        ));
+
+        drop(silent);
+        assert!(handshake(Timeouts::default().read).await.is_ok());
+
+        shutdown.cancel();
+        assert!(server.await.unwrap().is_ok());
     }
 
     #[tokio::test]
-    async fn test_dial_fail_missing_colon() {
+    async fn test_dial_all_shutdown() {
         STATIC_SK.get_or_init(|| SecretKey::random(&mut OsRng));
 
-        let bad_hostname = TEST_HOSTNAME.replace(':', "");
+        let enode: Enode = TEST_ENODE.parse().unwrap();
 
-        let enode = Enode {
-            username: TEST_USERNAME.to_string(),
-            hostname: bad_hostname.clone(),
+        let shutdown = CancellationToken::new();
+        shutdown.cancel();
+
+        let report = dial_all(
+            STATIC_SK.get().unwrap(),
+            DialOptions::default(),
+            vec![enode],
+            CONCURRENCY,
+            shutdown,
+        )
+        .await;
+
+        assert_eq!(1, report.entries.len());
+        assert_eq!(DialOutcome::Cancelled, report.entries[0].outcome);
+        assert_eq!(DialVerdict::AllFailed, report.verdict(None));
+    }
+
+    #[tokio::test]
+    async fn test_dial_all_shutdown_before_last() {
+        // a recipient that accepts connections but never answers
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let silent = tokio::spawn(async move { listener.accept().await });
+
+        let first = Enode {
+            address: address.into(),
+            ..TEST_ENODE.parse().unwrap()
+        };
+        let last: Enode = TEST_ENODE.parse().unwrap();
+        let options = DialOptions {
+            timeouts: Timeouts {
+                read: Duration::from_millis(300),
+                ..Timeouts::default()
+            },
+            ..DialOptions::default()
         };
 
-        let result = dial(&STATIC_SK.get().unwrap(), TIMEOUT, enode).await;
+        // interrupted while the first recipient is being dialed, one at a time
+        let shutdown = CancellationToken::new();
+        tokio::spawn({
+            let shutdown = shutdown.clone();
+            async move {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                shutdown.cancel();
+            }
+        });
 
-        assert!(result.is_err());
+        let report = dial_all(
+            &SecretKey::random(&mut OsRng),
+            options,
+            vec![first.clone(), last.clone()],
+            1,
+            shutdown,
+        )
+        .await;
+
+        assert_eq!(2, report.entries.len());
+        assert_eq!(first, report.entries[0].enode);
         assert_eq!(
-            Err(ConnectionError(TcpStreamError(
-                "invalid socket address".to_string()
-            ))),
-            result
+            DialOutcome::Timeout(DialPhase::Read),
+            report.entries[0].outcome
         );
+        assert_eq!(last, report.entries[1].enode);
+        assert_eq!(DialOutcome::Cancelled, report.entries[1].outcome);
+        assert!(report.entries[1].attempts.is_empty());
+        assert_ne!(DialVerdict::Passed, report.verdict(None));
+        silent.abort();
+    }
+
+    #[tokio::test]
+    async fn test_dial_all_concurrent() {
+        let (node_id, address, shutdown, server) =
+            spawn_recipient("127.0.0.1:0", MAX_INBOUND).await;
+
+        let enodes = (0..5)
+            .map(|_| Enode {
+                node_id,
+                address: address.into(),
+                discovery_port: None,
+            })
+            .collect();
+
+        let report = dial_all(
+            &SecretKey::random(&mut OsRng),
+            DialOptions::default(),
+            enodes,
+            2,
+            CancellationToken::new(),
+        )
+        .await;
+
+        assert_eq!(5, report.entries.len());
+        assert_eq!(5, report.successes());
+        assert!(report.entries.iter().all(|entry| entry.hello.is_some()));
+
+        shutdown.cancel();
+        assert!(server.await.unwrap().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_dial_read_timeout() {
+        // a recipient that accepts connections but never answers
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let silent = tokio::spawn(async move { listener.accept().await });
+
+        let enode = Enode {
+            address: address.into(),
+            ..TEST_ENODE.parse().unwrap()
+        };
+        let timeouts = Timeouts {
+            read: Duration::from_millis(100),
+            ..Timeouts::default()
+        };
+
+        let entry = dial(
+            &SecretKey::random(&mut OsRng),
+            &DialOptions {
+                timeouts,
+                ..DialOptions::default()
+            },
+            None,
+            enode,
+            CancellationToken::new(),
+        )
+        .await;
+
+        assert_eq!(DialOutcome::Timeout(DialPhase::Read), entry.outcome);
+        silent.abort();
+    }
+
+    #[tokio::test]
+    async fn test_dial_all_deadline() {
+        STATIC_SK.get_or_init(|| SecretKey::random(&mut OsRng));
+
+        let enode: Enode = TEST_ENODE.parse().unwrap();
+        let timeouts = Timeouts {
+            deadline: Some(Duration::ZERO),
+            ..Timeouts::default()
+        };
+
+        let report = dial_all(
+            STATIC_SK.get().unwrap(),
+            DialOptions {
+                timeouts,
+                ..DialOptions::default()
+            },
+            vec![enode.clone(), enode],
+            1,
+            CancellationToken::new(),
+        )
+        .await;
+
+        assert_eq!(2, report.entries.len());
+        assert!(report
+            .entries
+            .iter()
+            .all(|entry| entry.outcome == DialOutcome::Timeout(DialPhase::Deadline)));
+    }
+
+    #[tokio::test]
+    async fn test_dial_wrong_node_id_not_retried() {
+        let (_, address, shutdown, server) = spawn_recipient("127.0.0.1:0", MAX_INBOUND).await;
+
+        // the node ID of another node, at the recipient's address
+        let enode = Enode {
+            node_id: NodeId::from_secret_key(&SecretKey::random(&mut OsRng)),
+            address: address.into(),
+            discovery_port: None,
+        };
+        let options = DialOptions {
+            retry_policy: RetryPolicy {
+                retries: 2,
+                backoff_base: Duration::from_millis(10),
+                backoff_max: Duration::from_millis(20),
+                jitter: false,
+            },
+            ..DialOptions::default()
+        };
+
+        let entry = dial(
+            &SecretKey::random(&mut OsRng),
+            &options,
+            None,
+            enode,
+            CancellationToken::new(),
+        )
+        .await;
+
+        assert!(matches!(entry.outcome, DialOutcome::InvalidNodeKey(_)));
+        assert_eq!(1, entry.attempts.len());
+
+        shutdown.cancel();
+        assert!(server.await.unwrap().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_dial_retries() {
+        // a port that nothing listens on
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        drop(listener);
+
+        let enode = Enode {
+            address: address.into(),
+            ..TEST_ENODE.parse().unwrap()
+        };
+        let retry_policy = RetryPolicy {
+            retries: 2,
+            backoff_base: Duration::from_millis(10),
+            backoff_max: Duration::from_millis(20),
+            jitter: false,
+        };
+
+        let entry = dial(
+            &SecretKey::random(&mut OsRng),
+            &DialOptions {
+                retry_policy,
+                ..DialOptions::default()
+            },
+            None,
+            enode,
+            CancellationToken::new(),
+        )
+        .await;
+
+        assert!(!entry.outcome.is_success());
+        assert_eq!(3, entry.attempts.len());
+        assert_eq!(entry.outcome, entry.attempts[2].outcome);
     }
 }
@@ -6,9 +6,21 @@
 //!
 //! The library crate.
 
+pub mod codec;
 pub mod constants;
+pub mod ecies;
 pub mod errors;
 pub mod handshake;
+pub mod identity;
 pub mod input;
 pub mod interface;
+pub mod messages;
+pub mod output;
+pub mod report;
+pub mod resolver;
+pub mod retry;
+pub mod secrets;
 pub mod telemetry;
+
+#[cfg(test)]
+mod test_utils;
@@ -6,63 +6,367 @@
 //!
 //! The binary (executable) crate.
 
-use std::time::Instant;
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::time::{Duration, Instant};
 
-use k256::SecretKey;
-use rand_core::OsRng;
-
-use ethereum_handshake::input::{parse_cli_args, parse_file_enodes};
-use ethereum_handshake::interface::{answer, dial_all};
+use ethereum_handshake::constants::{
+    EXIT_ALL_FAILED, EXIT_BAD_INPUT, EXIT_RUNTIME_ERROR, EXIT_SOME_FAILED, EXIT_SUCCESS,
+};
+use ethereum_handshake::identity::{
+    generate_static_secret_key, load_nodekey, load_static_secret_key, KeySource, NodeId,
+};
+use ethereum_handshake::input::{
+    parse_cli_args, parse_file_enodes, Command, EnodeAddress, ParsedArgs,
+};
+use ethereum_handshake::interface::{
+    answer, bind, dial_all, serve, shutdown_on_ctrl_c, DialOptions,
+};
+use ethereum_handshake::output::{write_report, OutputFormat};
+use ethereum_handshake::report::{DialReport, DialVerdict, Timings};
+use ethereum_handshake::resolver::Resolver;
 use ethereum_handshake::telemetry::init_tracing;
 
 /// The program's entry point
 ///
-/// The application can work as an initiator of a connection, or as a recipient.
+/// The application can work as an initiator of a connection, or as a recipient,
+/// or as both at the same time, depending on the subcommand.
 ///
 /// This means that it can dial another node, initiating a handshake procedure,
 /// and that it can also receive a call from another node, responding to a
-/// handshake procedure (not implemented).
+/// handshake procedure.
 ///
+/// - Parses CLI arguments, the environment, and the configuration file
 /// - Sets up a tracing subscriber
-/// - Parses CLI arguments
+/// - Runs the subcommand
+/// - Exits with a code that tells whether the subcommand, e.g., the handshakes,
+///   succeeded; see the exit code constants
+/// - Prints a runtime error, and exits with its own code, so that it can't be
+///   mistaken for failed handshakes
+#[tokio::main]
+async fn main() -> ExitCode {
+    let parsed_args = parse_cli_args();
+
+    init_tracing(parsed_args.log_level.as_deref());
+
+    let exit_code = match parsed_args.command {
+        Command::Keygen | Command::Whoami => run_identity_command(
+            parsed_args.command,
+            &parsed_args.key_source,
+            parsed_args.listen_address,
+        ),
+        _ => run_handshakes(parsed_args).await.unwrap_or_else(|err| {
+            eprintln!("Error: {:?}", err);
+            EXIT_RUNTIME_ERROR
+        }),
+    };
+
+    ExitCode::from(exit_code)
+}
+
+/// Runs a subcommand that makes handshakes
+///
+/// - Loads our node's identity, and the hosts file, if one is given
 /// - Calls the handshake procedure
+/// - Prints or writes the dial results
 /// - Prints the total execution time
-#[tokio::main]
-async fn main() -> eyre::Result<()> {
+///
+/// # Returns
+/// The process exit code
+///
+/// # Errors
+/// - Binding to the listen address failed, or writing the dial results failed
+async fn run_handshakes(parsed_args: ParsedArgs) -> eyre::Result<u8> {
     let start = Instant::now();
 
-    init_tracing();
+    let static_secret_key = match load_static_secret_key(&parsed_args.key_source) {
+        Ok(static_secret_key) => static_secret_key,
+        Err(err) => {
+            eprintln!("{}", err);
+            return Ok(EXIT_BAD_INPUT);
+        }
+    };
+    let resolver = match &parsed_args.hosts_file {
+        Some(path) => match Resolver::from_hosts_file(path) {
+            Ok(resolver) => resolver,
+            Err(err) => {
+                eprintln!("{}", err);
+                return Ok(EXIT_BAD_INPUT);
+            }
+        },
+        None => Resolver::System,
+    };
+    let command = parsed_args.command;
+    let timeouts = parsed_args.timeouts;
+    let concurrency = parsed_args.concurrency;
+    let retry_policy = parsed_args.retry_policy;
+    let listen_address = parsed_args.listen_address;
+    let max_inbound = parsed_args.max_inbound;
+    let output = parsed_args.output;
+    let output_file = parsed_args.output_file;
+    let fail_threshold = parsed_args.fail_threshold;
+    // the standard output is reserved for machine-readable records, if they go there
+    let quiet = output.is_some() && output_file.is_none();
+    let shutdown = shutdown_on_ctrl_c();
+    let options = DialOptions {
+        timeouts,
+        retry_policy,
+        resolver,
+        listen_port: 0,
+    };
 
-    let static_secret_key = get_static_private_key();
+    let mut enodes = Vec::new();
+    if command != Command::Listen {
+        let cli_enodes = parsed_args.cli_enodes;
+        let file_enodes = parse_file_enodes(parsed_args.file_path).unwrap_or_else(|err| {
+            eprintln!("{}", err);
+            Vec::new()
+        });
+        enodes.reserve(cli_enodes.len() + file_enodes.len());
+        enodes.extend(cli_enodes);
+        enodes.extend(file_enodes);
 
-    let parsed_args = parse_cli_args();
-    let timeout = parsed_args.timeout;
-    let cli_enodes = parsed_args.cli_enodes;
-    let file_enodes = parse_file_enodes(parsed_args.file_path).unwrap_or_else(|err| {
-        eprintln!("{}", err);
-        Vec::new()
-    });
-    let mut enodes = Vec::with_capacity(cli_enodes.len() + file_enodes.len());
-    enodes.extend(cli_enodes);
-    enodes.extend(file_enodes);
-
-    // TODO: tokio::select maybe, but handshakes might not be atomic in that case, or they will be?
-    if !enodes.is_empty() {
-        dial_all(&static_secret_key, timeout, enodes).await?;
+        if enodes.is_empty() {
+            eprintln!("No valid recipient enodes were provided.");
+            return Ok(EXIT_BAD_INPUT);
+        }
+    }
+
+    // Each handshake runs on its own task in both directions, so it's atomic,
+    // and a shutdown only stops new handshakes from starting.
+    let exit_code = match command {
+        Command::Listen => {
+            answer(
+                &static_secret_key,
+                listen_address,
+                timeouts,
+                max_inbound,
+                shutdown,
+            )
+            .await?;
+            EXIT_SUCCESS
+        }
+        Command::Serve => {
+            // bound before dialing, so that a failure to bind stops the run at once,
+            // and so that our Hello advertises the port that is actually bound
+            let listener = bind(listen_address).await?;
+            let options = DialOptions {
+                listen_port: listener.local_addr()?.port(),
+                ..options
+            };
+
+            // The dial results are emitted as soon as dialing is done, and the listening
+            // side is done when it's interrupted (Ctrl+C), or when the deadline passes.
+            let dialing = async {
+                let report = dial_all(
+                    &static_secret_key,
+                    options,
+                    enodes,
+                    concurrency,
+                    shutdown.clone(),
+                )
+                .await;
+                let emitted = emit_report(&report, output, output_file);
+                if emitted.is_err() {
+                    shutdown.cancel();
+                } else if !shutdown.is_cancelled() {
+                    eprintln!("Dialing is done; listening until interrupted (Ctrl+C), or until the deadline.");
+                }
+                (report, emitted)
+            };
+            let ((report, emitted), answered) = tokio::join!(
+                dialing,
+                serve(
+                    &static_secret_key,
+                    listener,
+                    timeouts,
+                    max_inbound,
+                    shutdown.clone()
+                ),
+            );
+            emitted?;
+            answered?;
+            exit_code(report.verdict(fail_threshold))
+        }
+        _ => {
+            let report = dial_all(&static_secret_key, options, enodes, concurrency, shutdown).await;
+            match command {
+                Command::Inspect => print_inspection(&report),
+                Command::Crawl => print_census(&report),
+                _ => emit_report(&report, output, output_file)?,
+            }
+            exit_code(report.verdict(fail_threshold))
+        }
+    };
+
+    if quiet {
+        eprintln!("\nTook {:.3?} to complete.", start.elapsed());
     } else {
-        answer(timeout).await?;
+        println!("\nTook {:.3?} to complete.", start.elapsed());
+    }
+
+    Ok(exit_code)
+}
+
+/// Runs a subcommand that only works with our node's identity, and prints it
+///
+/// - `keygen` generates a new node key, and saves it to the node key file or to the keystore
+/// - `whoami` loads an existing node key
+///
+/// # Returns
+/// The process exit code
+fn run_identity_command(
+    command: Command,
+    key_source: &KeySource,
+    listen_address: SocketAddr,
+) -> u8 {
+    let static_secret_key = match (command, key_source) {
+        (Command::Keygen, source) => generate_static_secret_key(source),
+        (_, KeySource::Random) => {
+            eprintln!("A node key is needed: --nodekey, --nodekey-hex or --keystore.");
+            return EXIT_BAD_INPUT;
+        }
+        // unlike when dialing, a missing node key file isn't generated
+        (_, KeySource::NodeKeyFile(path)) => load_nodekey(path),
+        (_, source) => load_static_secret_key(source),
+    };
+    let static_secret_key = match static_secret_key {
+        Ok(static_secret_key) => static_secret_key,
+        Err(err) => {
+            eprintln!("{}", err);
+            return EXIT_BAD_INPUT;
+        }
+    };
+
+    let node_id = NodeId::from_secret_key(&static_secret_key);
+    println!("Node ID:   {}", node_id);
+    println!("Node hash: {:?}", node_id.hash());
+    println!("Enode:     {}", node_id.enode(listen_address));
+
+    if listen_address.ip().is_unspecified() {
+        eprintln!(
+            "Warning: the enode's address {} can't be dialed; set our node's address with --listen-address.",
+            listen_address.ip()
+        );
+    }
+
+    EXIT_SUCCESS
+}
+
+/// Maps the judgement of the dial results to the process exit code
+fn exit_code(verdict: DialVerdict) -> u8 {
+    match verdict {
+        DialVerdict::Passed => EXIT_SUCCESS,
+        DialVerdict::SomeFailed => EXIT_SOME_FAILED,
+        DialVerdict::AllFailed => EXIT_ALL_FAILED,
     }
+}
 
-    println!("\nTook {:.3?} to complete.", start.elapsed());
+/// Writes the dial results in the machine-readable format, if one is requested,
+/// to the output file, or to the standard output;
+/// otherwise prints them in a human-readable form
+fn emit_report(
+    report: &DialReport,
+    output: Option<OutputFormat>,
+    output_file: Option<PathBuf>,
+) -> eyre::Result<()> {
+    match (output, output_file) {
+        (Some(format), Some(path)) => {
+            write_report(report, format, BufWriter::new(File::create(path)?))?
+        }
+        (Some(format), None) => write_report(report, format, io::stdout().lock())?,
+        (None, _) => print_report(report),
+    }
 
     Ok(())
 }
 
-/// Simulate reading of a static secp256k1 private key
-/// from a permanent (non-volatile) storage
-fn get_static_private_key() -> SecretKey {
-    let static_secret_key: SecretKey = SecretKey::random(&mut OsRng);
-    // let sk = Secret::new(sk.to_bytes());
+/// Prints an outcome line per dialed recipient node
+///
+/// A host name is followed by the address that we connected to.
+fn print_report(report: &DialReport) {
+    println!();
+    for entry in &report.entries {
+        let address = match (&entry.enode.address, entry.address) {
+            (EnodeAddress::Dns(..), Some(address)) => {
+                format!("{} ({})", entry.enode.address, address)
+            }
+            _ => entry.enode.address.to_string(),
+        };
+        println!(
+            "{}: {} ({:.3?})",
+            address, entry.outcome, entry.timings.total
+        );
+    }
+}
+
+/// Prints everything that is learned about the single dialed recipient node
+fn print_inspection(report: &DialReport) {
+    let Some(entry) = report.entries.first() else {
+        return;
+    };
+    let latency = |latency: Option<Duration>| {
+        latency.map_or_else(|| "-".to_string(), |latency| format!("{:.3?}", latency))
+    };
+    let Timings {
+        connect,
+        handshake,
+        total,
+    } = entry.timings;
 
-    static_secret_key
+    println!();
+    println!("Node ID:          {}", entry.enode.node_id);
+    println!("Node hash:        {:?}", entry.enode.node_id.hash());
+    println!("Address:          {}", entry.enode.address);
+    println!("Discovery port:   {}", entry.enode.udp_port());
+    if let Some(address) = entry.address {
+        println!("Connected to:     {}", address);
+    }
+    println!("Outcome:          {}", entry.outcome);
+    println!("Attempts:         {}", entry.attempts.len());
+    println!("Connect:          {}", latency(connect));
+    println!("Handshake:        {}", latency(handshake));
+    println!("Total:            {:.3?}", total);
+
+    if let Some(hello) = &entry.hello {
+        let capabilities: Vec<String> =
+            hello.capabilities.iter().map(ToString::to_string).collect();
+
+        println!("Client ID:        {}", hello.client_id);
+        println!("Protocol version: {}", hello.protocol_version);
+        println!("Capabilities:     {}", capabilities.join(" "));
+        println!("Listen port:      {}", hello.listen_port);
+    }
+}
+
+/// Prints the counts of the dial outcomes, and of the recipients' clients and capabilities
+fn print_census(report: &DialReport) {
+    let census = report.census();
+
+    println!();
+    println!(
+        "Dialed {} recipient nodes: {} succeeded, {} failed.",
+        report.entries.len(),
+        report.successes(),
+        report.failures()
+    );
+    print_counts("Outcomes", census.outcomes);
+    print_counts("Clients", census.clients);
+    print_counts("Capabilities", census.capabilities);
+}
+
+/// Prints a titled list of counts, from the most common to the least common
+fn print_counts<K: Display>(title: &str, counts: BTreeMap<K, usize>) {
+    let mut counts: Vec<_> = counts.into_iter().collect();
+    counts.sort_by(|(_, a), (_, b)| b.cmp(a));
+
+    println!("\n{}:", title);
+    for (name, count) in counts {
+        println!("  {}: {}", name, count);
+    }
 }
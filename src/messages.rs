@@ -0,0 +1,436 @@
+//! The `p2p` capability messages that are exchanged at the end of the handshake
+//!
+//! - Hello
+//! - Disconnect
+//!
+//! The messages are specified at:
+//!
+//! [RLPx Transport Protocol: `p2p` Capability](https://github.com/ethereum/devp2p/blob/master/rlpx.md#p2p-capability)
+//!
+//! The first frame that either side sends is a Hello message.
+//! Hello messages are never Snappy-compressed,
+//! but once both sides have sent a Hello with `p2p` version 5 or later,
+//! every later message has Snappy-compressed `msg-data`.
+
+use std::fmt;
+
+use bytes::Bytes;
+use ethereum_types::H512;
+use k256::PublicKey;
+use rlp::{Decodable, DecoderError, Encodable, PayloadInfo, Rlp, RlpStream};
+
+use crate::constants::{
+    CAPABILITIES, CLIENT_ID, DISCONNECT_MSG_ID, HELLO_MSG_ID, P2P_PROTOCOL_VERSION,
+    SNAPPY_MAX_SHORT_LITERAL,
+};
+use crate::errors::HandshakeError;
+use crate::identity::NodeId;
+
+/// A `p2p` capability message
+#[derive(Clone, Debug, PartialEq)]
+pub enum P2pMessage {
+    Hello(Hello),
+    Disconnect(DisconnectReason),
+}
+
+impl P2pMessage {
+    /// Encodes the message as `frame-data`, i.e., `msg-id || msg-data`
+    pub fn encode(&self) -> Bytes {
+        let (msg_id, msg_data) = self.encode_parts();
+
+        [msg_id, msg_data].concat().into()
+    }
+
+    /// Encodes the message as `frame-data` with Snappy-compressed `msg-data`,
+    /// i.e., `msg-id || snappy(msg-data)`
+    ///
+    /// This is the encoding of every message after the Hello exchange,
+    /// if both sides have sent a Hello with `p2p` version 5 or later.
+    pub fn encode_compressed(&self) -> Bytes {
+        let (msg_id, msg_data) = self.encode_parts();
+
+        [msg_id, snappy_compress(&msg_data)].concat().into()
+    }
+
+    /// Encodes the message as its RLP-encoded `msg-id` and `msg-data`
+    fn encode_parts(&self) -> (Vec<u8>, Vec<u8>) {
+        let mut msg_data = RlpStream::new();
+
+        let msg_id = match self {
+            Self::Hello(hello) => {
+                msg_data.append(hello);
+                HELLO_MSG_ID
+            }
+            Self::Disconnect(reason) => {
+                msg_data.begin_list(1).append(&u8::from(*reason));
+                DISCONNECT_MSG_ID
+            }
+        };
+
+        (rlp::encode(&msg_id).to_vec(), msg_data.out().to_vec())
+    }
+
+    /// Decodes a message from `frame-data`, i.e., `msg-id || msg-data`
+    ///
+    /// # Errors
+    /// - [`HandshakeError::UnexpectedMessage`], if it isn't a Hello or a Disconnect message
+    /// - [`HandshakeError::RlpDecodeError`], if the message isn't properly encoded
+    pub fn decode(frame_data: &[u8]) -> Result<Self, HandshakeError> {
+        let msg_id_len = PayloadInfo::from(frame_data)?.total();
+        let (msg_id, msg_data) = frame_data
+            .split_at_checked(msg_id_len)
+            .ok_or(DecoderError::RlpIsTooShort)?;
+        let id: u64 = Rlp::new(msg_id).as_val()?;
+        let msg_data = Rlp::new(msg_data);
+
+        match id {
+            HELLO_MSG_ID => Ok(Self::Hello(msg_data.as_val()?)),
+            // the reason may or may not be wrapped in a list, depending on the implementation
+            DISCONNECT_MSG_ID => {
+                let reason: u8 = if msg_data.is_list() {
+                    msg_data.val_at(0)?
+                } else {
+                    msg_data.as_val()?
+                };
+                Ok(Self::Disconnect(DisconnectReason::from(reason)))
+            }
+            id => Err(HandshakeError::UnexpectedMessage(id)),
+        }
+    }
+}
+
+/// Compresses the data into the Snappy block format, as a single literal
+///
+/// The p2p messages that we send are tiny, so the format's copy elements
+/// wouldn't save anything, and any Snappy decompressor accepts a literal-only block.
+///
+/// `block = varint(len(data)) || tag || data`, where a literal's tag is `(len(data) - 1) << 2`,
+/// or, for longer data, `(59 + n) << 2` followed by `len(data) - 1` in `n` little-endian bytes.
+fn snappy_compress(data: &[u8]) -> Vec<u8> {
+    let mut block = Vec::with_capacity(data.len() + 10);
+
+    // the uncompressed length, as a little-endian base-128 varint
+    let mut length = data.len();
+    while length >= 0x80 {
+        block.push((length as u8 & 0x7f) | 0x80);
+        length >>= 7;
+    }
+    block.push(length as u8);
+
+    if !data.is_empty() {
+        let literal_len = data.len() - 1;
+        if literal_len < SNAPPY_MAX_SHORT_LITERAL {
+            block.push((literal_len as u8) << 2);
+        } else {
+            let len_bytes = literal_len.to_le_bytes();
+            let n = len_bytes.iter().rposition(|&b| b != 0).unwrap_or(0) + 1;
+            block.push(((59 + n) as u8) << 2);
+            block.extend_from_slice(&len_bytes[..n]);
+        }
+        block.extend_from_slice(data);
+    }
+
+    block
+}
+
+/// The Hello message
+///
+/// `[protocolVersion, clientId, [[cap1, capVersion1], ...], listenPort, nodeKey, ...]`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Hello {
+    pub protocol_version: u64,
+    pub client_id: String,
+    pub capabilities: Vec<Capability>,
+    pub listen_port: u16,
+    pub node_id: H512,
+}
+
+impl Hello {
+    /// Creates our own Hello message
+    ///
+    /// `listen_port` should be zero if we aren't listening for connections.
+    pub fn new(public_key: &PublicKey, listen_port: u16) -> Self {
+        let capabilities = CAPABILITIES
+            .iter()
+            .map(|(name, version)| Capability {
+                name: name.to_string(),
+                version: *version,
+            })
+            .collect();
+
+        Self {
+            protocol_version: P2P_PROTOCOL_VERSION,
+            client_id: CLIENT_ID.to_string(),
+            capabilities,
+            listen_port,
+            node_id: H512::from(NodeId::from(public_key).as_bytes()),
+        }
+    }
+
+    /// The name of the peer's client, i.e., the client ID up to its first `/`,
+    /// such as `Geth` in `Geth/v1.14.0-stable/linux-amd64/go1.22.4`
+    pub fn client_name(&self) -> &str {
+        self.client_id.split('/').next().unwrap_or_default()
+    }
+}
+
+impl Encodable for Hello {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        s.begin_list(5);
+        s.append(&self.protocol_version);
+        s.append(&self.client_id);
+        s.append_list(&self.capabilities);
+        s.append(&self.listen_port);
+        s.append(&self.node_id);
+    }
+}
+
+/// Additional list elements are ignored, as required by EIP-8.
+impl Decodable for Hello {
+    fn decode(rlp: &Rlp) -> Result<Self, DecoderError> {
+        Ok(Self {
+            protocol_version: rlp.val_at(0)?,
+            client_id: rlp.val_at(1)?,
+            capabilities: rlp.list_at(2)?,
+            listen_port: rlp.val_at(3)?,
+            node_id: rlp.val_at(4)?,
+        })
+    }
+}
+
+impl fmt::Display for Hello {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let capabilities = self
+            .capabilities
+            .iter()
+            .map(Capability::to_string)
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        write!(
+            f,
+            "{} (p2p/{}) [{}]",
+            self.client_id, self.protocol_version, capabilities
+        )
+    }
+}
+
+/// A capability (a sub-protocol) that a node supports, such as `eth/68`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Capability {
+    pub name: String,
+    pub version: u64,
+}
+
+impl Encodable for Capability {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        s.begin_list(2);
+        s.append(&self.name);
+        s.append(&self.version);
+    }
+}
+
+impl Decodable for Capability {
+    fn decode(rlp: &Rlp) -> Result<Self, DecoderError> {
+        Ok(Self {
+            name: rlp.val_at(0)?,
+            version: rlp.val_at(1)?,
+        })
+    }
+}
+
+impl fmt::Display for Capability {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.name, self.version)
+    }
+}
+
+/// The reason for a Disconnect message
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DisconnectReason {
+    Requested,
+    TcpError,
+    ProtocolBreach,
+    UselessPeer,
+    TooManyPeers,
+    AlreadyConnected,
+    IncompatibleVersion,
+    InvalidIdentity,
+    ClientQuitting,
+    UnexpectedIdentity,
+    ConnectedToSelf,
+    PingTimeout,
+    SubprotocolError,
+    Unknown(u8),
+}
+
+impl From<u8> for DisconnectReason {
+    fn from(reason: u8) -> Self {
+        match reason {
+            0x00 => Self::Requested,
+            0x01 => Self::TcpError,
+            0x02 => Self::ProtocolBreach,
+            0x03 => Self::UselessPeer,
+            0x04 => Self::TooManyPeers,
+            0x05 => Self::AlreadyConnected,
+            0x06 => Self::IncompatibleVersion,
+            0x07 => Self::InvalidIdentity,
+            0x08 => Self::ClientQuitting,
+            0x09 => Self::UnexpectedIdentity,
+            0x0a => Self::ConnectedToSelf,
+            0x0b => Self::PingTimeout,
+            0x10 => Self::SubprotocolError,
+            other => Self::Unknown(other),
+        }
+    }
+}
+
+impl From<DisconnectReason> for u8 {
+    fn from(reason: DisconnectReason) -> Self {
+        match reason {
+            DisconnectReason::Requested => 0x00,
+            DisconnectReason::TcpError => 0x01,
+            DisconnectReason::ProtocolBreach => 0x02,
+            DisconnectReason::UselessPeer => 0x03,
+            DisconnectReason::TooManyPeers => 0x04,
+            DisconnectReason::AlreadyConnected => 0x05,
+            DisconnectReason::IncompatibleVersion => 0x06,
+            DisconnectReason::InvalidIdentity => 0x07,
+            DisconnectReason::ClientQuitting => 0x08,
+            DisconnectReason::UnexpectedIdentity => 0x09,
+            DisconnectReason::ConnectedToSelf => 0x0a,
+            DisconnectReason::PingTimeout => 0x0b,
+            DisconnectReason::SubprotocolError => 0x10,
+            DisconnectReason::Unknown(other) => other,
+        }
+    }
+}
+
+impl fmt::Display for DisconnectReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let reason = match self {
+            Self::Requested => "disconnect requested",
+            Self::TcpError => "TCP sub-system error",
+            Self::ProtocolBreach => "breach of protocol",
+            Self::UselessPeer => "useless peer",
+            Self::TooManyPeers => "too many peers",
+            Self::AlreadyConnected => "already connected",
+            Self::IncompatibleVersion => "incompatible P2P protocol version",
+            Self::InvalidIdentity => "null node identity received",
+            Self::ClientQuitting => "client quitting",
+            Self::UnexpectedIdentity => "unexpected identity in handshake",
+            Self::ConnectedToSelf => "identity is the same as this node",
+            Self::PingTimeout => "ping timeout",
+            Self::SubprotocolError => "some other reason specific to a subprotocol",
+            Self::Unknown(other) => return write!(f, "unknown reason {:#04x}", other),
+        };
+
+        f.write_str(reason)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use k256::SecretKey;
+    use rand_core::OsRng;
+
+    use super::*;
+
+    #[test]
+    fn test_hello_encode_decode_pass() {
+        let public_key = SecretKey::random(&mut OsRng).public_key();
+        let hello = P2pMessage::Hello(Hello::new(&public_key, 30303));
+
+        let frame_data = hello.encode();
+        assert_eq!(0x80, frame_data[0]);
+
+        let decoded = P2pMessage::decode(&frame_data).unwrap();
+        assert_eq!(hello, decoded);
+    }
+
+    #[test]
+    fn test_hello_decode_extra_elements() {
+        let public_key = SecretKey::random(&mut OsRng).public_key();
+        let hello = Hello::new(&public_key, 0);
+
+        let mut rlp_stream = RlpStream::new();
+        rlp_stream.append(&HELLO_MSG_ID);
+        rlp_stream.begin_list(6);
+        rlp_stream.append(&hello.protocol_version);
+        rlp_stream.append(&hello.client_id);
+        rlp_stream.append_list(&hello.capabilities);
+        rlp_stream.append(&hello.listen_port);
+        rlp_stream.append(&hello.node_id);
+        rlp_stream.append(&"extra");
+
+        let decoded = P2pMessage::decode(&rlp_stream.out()).unwrap();
+        assert_eq!(P2pMessage::Hello(hello), decoded);
+    }
+
+    #[test]
+    fn test_hello_client_name() {
+        let mut hello = Hello::new(&SecretKey::random(&mut OsRng).public_key(), 0);
+
+        hello.client_id = "Geth/v1.14.0-stable/linux-amd64/go1.22.4".to_string();
+        assert_eq!("Geth", hello.client_name());
+
+        hello.client_id = "reth".to_string();
+        assert_eq!("reth", hello.client_name());
+    }
+
+    #[test]
+    fn test_disconnect_encode_decode_pass() {
+        let disconnect = P2pMessage::Disconnect(DisconnectReason::TooManyPeers);
+
+        let decoded = P2pMessage::decode(&disconnect.encode()).unwrap();
+        assert_eq!(disconnect, decoded);
+    }
+
+    #[test]
+    fn test_disconnect_encode_compressed() {
+        let disconnect = P2pMessage::Disconnect(DisconnectReason::UnexpectedIdentity);
+
+        // msg-id, then the length varint, the literal tag, and the literal [0x09]
+        assert_eq!(
+            [0x01, 0x02, 0x04, 0xc1, 0x09],
+            disconnect.encode_compressed().as_ref()
+        );
+    }
+
+    #[test]
+    fn test_snappy_compress_long_literal() {
+        assert_eq!(vec![0x00], snappy_compress(&[]));
+
+        let data = [0xab; 300];
+        let block = snappy_compress(&data);
+        // 300 as a varint, then a tag with a two-byte length of 299
+        assert_eq!([0xac, 0x02, 0xf4, 0x2b, 0x01], block[..5]);
+        assert_eq!(data, block[5..]);
+    }
+
+    #[test]
+    fn test_disconnect_decode_bare_reason() {
+        let mut rlp_stream = RlpStream::new();
+        rlp_stream.append(&DISCONNECT_MSG_ID);
+        rlp_stream.append(&0x04u8);
+
+        let decoded = P2pMessage::decode(&rlp_stream.out()).unwrap();
+        assert_eq!(
+            P2pMessage::Disconnect(DisconnectReason::TooManyPeers),
+            decoded
+        );
+    }
+
+    #[test]
+    fn test_decode_fail_unexpected_message() {
+        let mut rlp_stream = RlpStream::new();
+        rlp_stream.append(&0x10u8);
+        rlp_stream.begin_list(0);
+
+        let result = P2pMessage::decode(&rlp_stream.out());
+        assert!(matches!(
+            result,
+            Err(HandshakeError::UnexpectedMessage(0x10))
+        ));
+    }
+}
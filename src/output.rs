@@ -0,0 +1,276 @@
+//! Machine-readable output of dial results
+//!
+//! Writes a [`Record`] per dialed recipient node, in one of the [`OutputFormat`]s.
+
+use std::io::Write;
+use std::time::Duration;
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::errors::OutputError;
+use crate::report::{DialEntry, DialReport};
+
+/// Supported machine-readable output formats
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    /// A JSON array of records
+    Json,
+    /// A JSON record per line
+    Ndjson,
+    /// A header line, followed by a comma-separated record per line
+    Csv,
+}
+
+/// The result of dialing a single recipient node, flattened for output
+///
+/// Latencies are in milliseconds, and are the ones of the last attempt;
+/// a latency is missing if its phase wasn't reached.
+///
+/// `connected_address` is the address that the last attempt connected to,
+/// which tells which of the resolved addresses of a host name was used.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Record {
+    pub node_id: String,
+    pub address: String,
+    pub connected_address: Option<String>,
+    pub outcome: &'static str,
+    pub error_class: Option<String>,
+    pub error: Option<String>,
+    pub attempts: usize,
+    pub connect_ms: Option<f64>,
+    pub handshake_ms: Option<f64>,
+    pub total_ms: f64,
+    pub client_id: Option<String>,
+    pub capabilities: Vec<String>,
+}
+
+impl Record {
+    const CSV_HEADER: &'static str =
+        "node_id,address,connected_address,outcome,error_class,error,attempts,\
+        connect_ms,handshake_ms,total_ms,client_id,capabilities";
+
+    /// Writes the record as a CSV line; capabilities are separated by spaces
+    fn write_csv(&self, writer: &mut impl Write) -> Result<(), OutputError> {
+        let optional = |value: &Option<String>| value.as_deref().map(csv_escape);
+        let latency = |value: Option<f64>| value.map(|ms| ms.to_string());
+
+        let fields = [
+            csv_escape(&self.node_id),
+            csv_escape(&self.address),
+            optional(&self.connected_address).unwrap_or_default(),
+            self.outcome.to_string(),
+            optional(&self.error_class).unwrap_or_default(),
+            optional(&self.error).unwrap_or_default(),
+            self.attempts.to_string(),
+            latency(self.connect_ms).unwrap_or_default(),
+            latency(self.handshake_ms).unwrap_or_default(),
+            self.total_ms.to_string(),
+            optional(&self.client_id).unwrap_or_default(),
+            csv_escape(&self.capabilities.join(" ")),
+        ];
+
+        writeln!(writer, "{}", fields.join(","))?;
+
+        Ok(())
+    }
+}
+
+impl From<&DialEntry> for Record {
+    fn from(entry: &DialEntry) -> Self {
+        let outcome = &entry.outcome;
+        let hello = entry.hello.as_ref();
+
+        Self {
+            node_id: entry.enode.node_id.to_string(),
+            address: entry.enode.address.to_string(),
+            connected_address: entry.address.map(|address| address.to_string()),
+            outcome: outcome.kind(),
+            error_class: outcome.error_class(),
+            error: (!outcome.is_success()).then(|| outcome.to_string()),
+            attempts: entry.attempts.len(),
+            connect_ms: entry.timings.connect.map(millis),
+            handshake_ms: entry.timings.handshake.map(millis),
+            total_ms: millis(entry.timings.total),
+            client_id: hello.map(|hello| hello.client_id.clone()),
+            capabilities: hello
+                .map(|hello| hello.capabilities.iter().map(ToString::to_string).collect())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// Writes a record per entry of the dial report, in the given format
+///
+/// # Errors
+/// - [`OutputError::JsonError`], if serialization fails
+/// - [`OutputError::IOError`], if writing fails
+pub fn write_report(
+    report: &DialReport,
+    format: OutputFormat,
+    mut writer: impl Write,
+) -> Result<(), OutputError> {
+    let records = report.entries.iter().map(Record::from);
+
+    match format {
+        OutputFormat::Json => {
+            serde_json::to_writer_pretty(&mut writer, &records.collect::<Vec<_>>())?;
+            writeln!(writer)?;
+        }
+        OutputFormat::Ndjson => {
+            for record in records {
+                serde_json::to_writer(&mut writer, &record)?;
+                writeln!(writer)?;
+            }
+        }
+        OutputFormat::Csv => {
+            writeln!(writer, "{}", Record::CSV_HEADER)?;
+            for record in records {
+                record.write_csv(&mut writer)?;
+            }
+        }
+    }
+
+    writer.flush()?;
+
+    Ok(())
+}
+
+/// Converts a duration to fractional milliseconds, with microsecond precision
+fn millis(duration: Duration) -> f64 {
+    duration.as_micros() as f64 / 1000.0
+}
+
+/// Quotes a CSV field if it contains a separator, a quote or a line break
+fn csv_escape(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use k256::SecretKey;
+    use rand_core::OsRng;
+
+    use crate::constants::{TEST_ENODE, TEST_HOSTNAME, TEST_USERNAME};
+    use crate::input::Enode;
+    use crate::messages::{DisconnectReason, Hello};
+    use crate::report::{DialAttempt, DialOutcome, Timings};
+
+    use super::*;
+
+    fn report() -> DialReport {
+        let enode: Enode = TEST_ENODE.parse().unwrap();
+        let hello = Hello::new(&SecretKey::random(&mut OsRng).public_key(), 0);
+        let succeeded = Timings {
+            connect: Some(Duration::from_millis(2)),
+            handshake: Some(Duration::from_millis(3)),
+            total: Duration::from_millis(5),
+        };
+        let disconnected = Timings {
+            connect: Some(Duration::from_millis(2)),
+            handshake: Some(Duration::from_millis(1)),
+            total: Duration::from_millis(3),
+        };
+        let too_many_peers = DialOutcome::Disconnected(DisconnectReason::TooManyPeers);
+
+        DialReport {
+            entries: vec![
+                DialEntry {
+                    enode: enode.clone(),
+                    address: Some(TEST_HOSTNAME.parse().unwrap()),
+                    outcome: DialOutcome::Success,
+                    hello: Some(hello),
+                    timings: succeeded,
+                    attempts: vec![DialAttempt {
+                        outcome: DialOutcome::Success,
+                        timings: succeeded,
+                    }],
+                },
+                DialEntry {
+                    enode,
+                    address: None,
+                    outcome: too_many_peers.clone(),
+                    hello: None,
+                    timings: disconnected,
+                    attempts: vec![
+                        DialAttempt {
+                            outcome: too_many_peers.clone(),
+                            timings: disconnected,
+                        },
+                        DialAttempt {
+                            outcome: too_many_peers,
+                            timings: disconnected,
+                        },
+                    ],
+                },
+            ],
+        }
+    }
+
+    fn write(format: OutputFormat) -> String {
+        let mut output = Vec::new();
+        write_report(&report(), format, &mut output).unwrap();
+
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn test_write_json() {
+        let output: serde_json::Value = serde_json::from_str(&write(OutputFormat::Json)).unwrap();
+        let records = output.as_array().unwrap();
+
+        assert_eq!(2, records.len());
+        assert_eq!("success", records[0]["outcome"]);
+        assert_eq!(
+            serde_json::json!(["eth/67", "eth/68"]),
+            records[0]["capabilities"]
+        );
+        assert_eq!("disconnect_0x04", records[1]["error_class"]);
+        assert!(records[1]["client_id"].is_null());
+    }
+
+    #[test]
+    fn test_write_ndjson() {
+        let output = write(OutputFormat::Ndjson);
+        let records: Vec<serde_json::Value> = output
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+
+        assert_eq!(2, records.len());
+        assert_eq!(TEST_USERNAME, records[0]["node_id"]);
+        assert_eq!(TEST_HOSTNAME, records[0]["connected_address"]);
+        assert_eq!(5.0, records[0]["total_ms"]);
+        assert_eq!("disconnected", records[1]["outcome"]);
+        assert_eq!(2, records[1]["attempts"]);
+        assert!(records[1]["connected_address"].is_null());
+    }
+
+    #[test]
+    fn test_write_csv() {
+        let output = write(OutputFormat::Csv);
+        let lines: Vec<&str> = output.lines().collect();
+
+        assert_eq!(3, lines.len());
+        assert_eq!(Record::CSV_HEADER, lines[0]);
+        assert!(lines[1].starts_with(&format!(
+            "{},{},{},success,,,1,2,3,5,",
+            TEST_USERNAME, TEST_HOSTNAME, TEST_HOSTNAME
+        )));
+        assert!(lines[1].ends_with(",eth/67 eth/68"));
+        assert!(lines[2].contains(&format!("{},,disconnected,", TEST_HOSTNAME)));
+        assert!(lines[2].contains(",disconnected,disconnect_0x04,disconnected: too many peers,2,"));
+    }
+
+    #[test]
+    fn test_csv_escape() {
+        assert_eq!("plain", csv_escape("plain"));
+        assert_eq!("\"a,b\"", csv_escape("a,b"));
+        assert_eq!("\"say \"\"hi\"\"\"", csv_escape("say \"hi\""));
+    }
+}
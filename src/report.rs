@@ -0,0 +1,471 @@
+//! Structured results of dialing recipient nodes
+//!
+//! [`crate::interface::dial_all`] returns a [`DialReport`], which holds
+//! a [`DialEntry`] per recipient enode, with the outcome of the dial,
+//! the recipient's Hello message if one arrived, timings, and every attempt.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use crate::errors::HandshakeError;
+use crate::input::Enode;
+use crate::messages::{DisconnectReason, Hello};
+
+/// The results of dialing all recipient nodes
+///
+/// Entries are in the order in which the dials finished.
+#[derive(Debug, Default)]
+pub struct DialReport {
+    pub entries: Vec<DialEntry>,
+}
+
+impl DialReport {
+    /// The number of recipients that we've successfully handshaken with
+    pub fn successes(&self) -> usize {
+        self.entries
+            .iter()
+            .filter(|entry| entry.outcome.is_success())
+            .count()
+    }
+
+    /// The number of recipients that we've failed to handshake with
+    pub fn failures(&self) -> usize {
+        self.entries.len() - self.successes()
+    }
+
+    /// Judges the dial results as a whole
+    ///
+    /// If `fail_threshold` is provided, the results pass as long as the percentage
+    /// of failed handshakes doesn't exceed it. Otherwise, a single failure fails them.
+    ///
+    /// If no handshake succeeded, including when no node was dialed
+    /// due to a shutdown, all of them are considered failed.
+    pub fn verdict(&self, fail_threshold: Option<u8>) -> DialVerdict {
+        let failures = self.failures();
+        let threshold = fail_threshold.unwrap_or(0) as usize;
+
+        if !self.entries.is_empty() && failures * 100 <= threshold * self.entries.len() {
+            DialVerdict::Passed
+        } else if self.successes() == 0 {
+            DialVerdict::AllFailed
+        } else {
+            DialVerdict::SomeFailed
+        }
+    }
+
+    /// Counts the outcomes of the dials, and the clients and capabilities
+    /// of the recipients that we've successfully handshaken with
+    pub fn census(&self) -> Census {
+        let mut census = Census::default();
+
+        for entry in &self.entries {
+            *census.outcomes.entry(entry.outcome.kind()).or_default() += 1;
+
+            if let Some(hello) = &entry.hello {
+                *census
+                    .clients
+                    .entry(hello.client_name().to_string())
+                    .or_default() += 1;
+
+                for capability in &hello.capabilities {
+                    *census
+                        .capabilities
+                        .entry(capability.to_string())
+                        .or_default() += 1;
+                }
+            }
+        }
+
+        census
+    }
+}
+
+/// Counts of the dial outcomes, and of the recipients' clients and capabilities
+#[derive(Debug, Default, PartialEq)]
+pub struct Census {
+    pub outcomes: BTreeMap<&'static str, usize>,
+    pub clients: BTreeMap<String, usize>,
+    pub capabilities: BTreeMap<String, usize>,
+}
+
+/// The judgement of the dial results as a whole
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DialVerdict {
+    Passed,
+    SomeFailed,
+    AllFailed,
+}
+
+/// The result of dialing a single recipient node
+///
+/// The outcome, the timings and the address are the ones of the last attempt.
+#[derive(Debug)]
+pub struct DialEntry {
+    pub enode: Enode,
+    /// The address that we connected to, which is one of the resolved ones
+    /// if the enode has a host name; `None` if no connection was established
+    pub address: Option<SocketAddr>,
+    pub outcome: DialOutcome,
+    pub hello: Option<Hello>,
+    pub timings: Timings,
+    pub attempts: Vec<DialAttempt>,
+}
+
+/// A single attempt at dialing a recipient node
+#[derive(Clone, Debug, PartialEq)]
+pub struct DialAttempt {
+    pub outcome: DialOutcome,
+    pub timings: Timings,
+}
+
+/// How dialing a recipient node ended
+#[derive(Clone, Debug, PartialEq)]
+pub enum DialOutcome {
+    /// The handshake is complete
+    Success,
+    /// The recipient's address is invalid
+    InvalidAddress(String),
+    /// The recipient's host name couldn't be resolved
+    Unresolved(String),
+    /// The recipient's node key, i.e., its public key, is presumably invalid;
+    /// the recipient closed the connection without sending any of its auth-ack,
+    /// which it does if it can't decrypt our auth message, because it was encrypted
+    /// to another key. It's a guess, as an overloaded recipient might do the same.
+    InvalidNodeKey(String),
+    /// The recipient refused the TCP connection
+    Refused,
+    /// A phase of the dial didn't complete in time
+    Timeout(DialPhase),
+    /// The TCP connection failed or broke for another reason
+    ConnectionError(String),
+    /// The recipient violated the handshake protocol, or failed authentication
+    ProtocolError(String),
+    /// The recipient sent a Disconnect message instead of its Hello message
+    Disconnected(DisconnectReason),
+    /// The recipient wasn't dialed, because the run was interrupted
+    Cancelled,
+}
+
+impl DialOutcome {
+    /// Whether the handshake is complete
+    pub fn is_success(&self) -> bool {
+        *self == Self::Success
+    }
+
+    /// A short, stable name of the outcome, e.g., for machine-readable output
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Success => "success",
+            Self::InvalidAddress(_) => "invalid_address",
+            Self::Unresolved(_) => "unresolved",
+            Self::InvalidNodeKey(_) => "invalid_node_key",
+            Self::Refused => "refused",
+            Self::Timeout(_) => "timeout",
+            Self::ConnectionError(_) => "connection_error",
+            Self::ProtocolError(_) => "protocol_error",
+            Self::Disconnected(_) => "disconnected",
+            Self::Cancelled => "cancelled",
+        }
+    }
+
+    /// A short, stable class of the failure, which is finer-grained than [`Self::kind`]
+    ///
+    /// # Returns
+    /// `None` on success
+    pub fn error_class(&self) -> Option<String> {
+        match self {
+            Self::Success => None,
+            Self::InvalidAddress(_) => Some("invalid_address".to_string()),
+            Self::Unresolved(_) => Some("resolve_error".to_string()),
+            Self::InvalidNodeKey(_) => Some("invalid_node_key".to_string()),
+            Self::Refused => Some("connection_refused".to_string()),
+            Self::Timeout(DialPhase::Deadline) => Some("deadline_exceeded".to_string()),
+            Self::Timeout(phase) => Some(format!("{}_timeout", phase)),
+            Self::ConnectionError(_) => Some("connection_error".to_string()),
+            Self::ProtocolError(_) => Some("protocol_error".to_string()),
+            Self::Disconnected(reason) => Some(format!("disconnect_{:#04x}", u8::from(*reason))),
+            Self::Cancelled => Some("cancelled".to_string()),
+        }
+    }
+
+    /// Whether dialing the recipient again might end differently
+    ///
+    /// Network and resolution failures, and timeouts of the individual phases are retryable,
+    /// as are disconnects for reasons that are likely temporary, such as
+    /// [`DisconnectReason::TooManyPeers`]. Invalid input, protocol errors,
+    /// and the deadline of the whole run are never retryable.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::Refused | Self::ConnectionError(_) | Self::Unresolved(_) => true,
+            Self::Timeout(phase) => *phase != DialPhase::Deadline,
+            Self::Disconnected(reason) => matches!(
+                reason,
+                DisconnectReason::TcpError
+                    | DisconnectReason::TooManyPeers
+                    | DisconnectReason::AlreadyConnected
+                    | DisconnectReason::ClientQuitting
+                    | DisconnectReason::PingTimeout
+            ),
+            Self::Success
+            | Self::InvalidAddress(_)
+            | Self::InvalidNodeKey(_)
+            | Self::ProtocolError(_)
+            | Self::Cancelled => false,
+        }
+    }
+
+    /// Classifies an error that occurred while connecting to a recipient
+    pub fn from_connect_error(err: &io::Error) -> Self {
+        match err.kind() {
+            io::ErrorKind::InvalidInput => Self::InvalidAddress(err.to_string()),
+            io::ErrorKind::ConnectionRefused => Self::Refused,
+            _ => Self::ConnectionError(err.to_string()),
+        }
+    }
+}
+
+impl From<HandshakeError> for DialOutcome {
+    fn from(err: HandshakeError) -> Self {
+        match err {
+            HandshakeError::Disconnected(reason) => Self::Disconnected(reason),
+            HandshakeError::ReadTimeout => Self::Timeout(DialPhase::Read),
+            HandshakeError::AuthRejected => Self::InvalidNodeKey(err.to_string()),
+            HandshakeError::IOError(_) | HandshakeError::ConnectionClosed => {
+                Self::ConnectionError(err.to_string())
+            }
+            _ => Self::ProtocolError(err.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for DialOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Success => f.write_str("success"),
+            Self::InvalidAddress(err) => write!(f, "invalid address: {}", err),
+            Self::Unresolved(err) => write!(f, "unresolved: {}", err),
+            Self::InvalidNodeKey(err) => write!(f, "invalid node key: {}", err),
+            Self::Refused => f.write_str("connection refused"),
+            Self::Timeout(DialPhase::Deadline) => f.write_str("deadline exceeded"),
+            Self::Timeout(phase) => write!(f, "{} timeout", phase),
+            Self::ConnectionError(err) => write!(f, "connection error: {}", err),
+            Self::ProtocolError(err) => write!(f, "protocol error: {}", err),
+            Self::Disconnected(reason) => write!(f, "disconnected: {}", reason),
+            Self::Cancelled => f.write_str("cancelled"),
+        }
+    }
+}
+
+/// A phase of dialing a recipient node
+///
+/// [`DialPhase::Read`] is a single read during the handshake, and
+/// [`DialPhase::Deadline`] stands for the deadline of the whole run,
+/// which can cut any phase short.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DialPhase {
+    Connect,
+    Handshake,
+    Read,
+    Deadline,
+}
+
+impl fmt::Display for DialPhase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Connect => f.write_str("connect"),
+            Self::Handshake => f.write_str("handshake"),
+            Self::Read => f.write_str("read"),
+            Self::Deadline => f.write_str("deadline"),
+        }
+    }
+}
+
+/// How long the phases of dialing a recipient node took
+///
+/// A phase is `None` if it wasn't reached.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Timings {
+    pub connect: Option<Duration>,
+    pub handshake: Option<Duration>,
+    pub total: Duration,
+}
+
+#[cfg(test)]
+mod tests {
+    use k256::SecretKey;
+    use rand_core::OsRng;
+
+    use crate::constants::TEST_ENODE;
+
+    use super::*;
+
+    fn entry(outcome: DialOutcome) -> DialEntry {
+        DialEntry {
+            enode: TEST_ENODE.parse().unwrap(),
+            address: None,
+            outcome,
+            hello: None,
+            timings: Timings::default(),
+            attempts: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_report_counts() {
+        let report = DialReport {
+            entries: vec![
+                entry(DialOutcome::Success),
+                entry(DialOutcome::Refused),
+                entry(DialOutcome::Timeout(DialPhase::Handshake)),
+            ],
+        };
+
+        assert_eq!(1, report.successes());
+        assert_eq!(2, report.failures());
+    }
+
+    #[test]
+    fn test_report_census() {
+        let mut geth = entry(DialOutcome::Success);
+        let mut hello = Hello::new(&SecretKey::random(&mut OsRng).public_key(), 0);
+        hello.client_id = "Geth/v1.14.0-stable/linux-amd64/go1.22.4".to_string();
+        geth.hello = Some(hello);
+        let report = DialReport {
+            entries: vec![
+                geth,
+                entry(DialOutcome::Refused),
+                entry(DialOutcome::Refused),
+            ],
+        };
+
+        let census = report.census();
+
+        assert_eq!(
+            BTreeMap::from([("refused", 2), ("success", 1)]),
+            census.outcomes
+        );
+        assert_eq!(BTreeMap::from([("Geth".to_string(), 1)]), census.clients);
+        assert_eq!(
+            BTreeMap::from([("eth/67".to_string(), 1), ("eth/68".to_string(), 1)]),
+            census.capabilities
+        );
+    }
+
+    #[test]
+    fn test_report_verdict() {
+        let report = DialReport {
+            entries: vec![
+                entry(DialOutcome::Success),
+                entry(DialOutcome::Success),
+                entry(DialOutcome::Success),
+                entry(DialOutcome::Refused),
+            ],
+        };
+
+        assert_eq!(DialVerdict::SomeFailed, report.verdict(None));
+        assert_eq!(DialVerdict::SomeFailed, report.verdict(Some(24)));
+        assert_eq!(DialVerdict::Passed, report.verdict(Some(25)));
+
+        let report = DialReport {
+            entries: vec![entry(DialOutcome::Refused), entry(DialOutcome::Refused)],
+        };
+
+        assert_eq!(DialVerdict::AllFailed, report.verdict(None));
+        assert_eq!(DialVerdict::Passed, report.verdict(Some(100)));
+
+        assert_eq!(
+            DialVerdict::AllFailed,
+            DialReport::default().verdict(Some(100))
+        );
+    }
+
+    #[test]
+    fn test_outcome_error_class() {
+        assert_eq!(None, DialOutcome::Success.error_class());
+        assert_eq!(
+            Some("connect_timeout".to_string()),
+            DialOutcome::Timeout(DialPhase::Connect).error_class()
+        );
+        assert_eq!(
+            Some("deadline_exceeded".to_string()),
+            DialOutcome::Timeout(DialPhase::Deadline).error_class()
+        );
+        assert_eq!(
+            Some("disconnect_0x04".to_string()),
+            DialOutcome::Disconnected(DisconnectReason::TooManyPeers).error_class()
+        );
+        assert_eq!(
+            Some("resolve_error".to_string()),
+            DialOutcome::Unresolved("no addresses".to_string()).error_class()
+        );
+    }
+
+    #[test]
+    fn test_outcome_is_retryable() {
+        assert!(DialOutcome::Refused.is_retryable());
+        assert!(DialOutcome::Timeout(DialPhase::Connect).is_retryable());
+        assert!(DialOutcome::Disconnected(DisconnectReason::TooManyPeers).is_retryable());
+        assert!(DialOutcome::Unresolved("no addresses".to_string()).is_retryable());
+
+        assert!(!DialOutcome::Success.is_retryable());
+        assert!(!DialOutcome::Timeout(DialPhase::Deadline).is_retryable());
+        assert!(!DialOutcome::InvalidNodeKey("bad".to_string()).is_retryable());
+        assert!(!DialOutcome::Disconnected(DisconnectReason::UselessPeer).is_retryable());
+    }
+
+    #[test]
+    fn test_outcome_from_connect_error() {
+        let refused = io::Error::from(io::ErrorKind::ConnectionRefused);
+        assert_eq!(
+            DialOutcome::Refused,
+            DialOutcome::from_connect_error(&refused)
+        );
+
+        let invalid = io::Error::new(io::ErrorKind::InvalidInput, "invalid");
+        assert_eq!(
+            DialOutcome::InvalidAddress("invalid".to_string()),
+            DialOutcome::from_connect_error(&invalid)
+        );
+
+        let unreachable = io::Error::other("unreachable");
+        assert_eq!(
+            DialOutcome::ConnectionError("unreachable".to_string()),
+            DialOutcome::from_connect_error(&unreachable)
+        );
+    }
+
+    #[test]
+    fn test_outcome_from_handshake_error() {
+        assert_eq!(
+            DialOutcome::Disconnected(DisconnectReason::TooManyPeers),
+            DialOutcome::from(HandshakeError::Disconnected(DisconnectReason::TooManyPeers))
+        );
+        assert_eq!(
+            DialOutcome::Timeout(DialPhase::Read),
+            DialOutcome::from(HandshakeError::ReadTimeout)
+        );
+        assert!(matches!(
+            DialOutcome::from(HandshakeError::ConnectionClosed),
+            DialOutcome::ConnectionError(_)
+        ));
+        assert!(matches!(
+            DialOutcome::from(HandshakeError::AuthRejected),
+            DialOutcome::InvalidNodeKey(_)
+        ));
+        assert!(matches!(
+            DialOutcome::from(HandshakeError::Sec1Error("bad".to_string())),
+            DialOutcome::ProtocolError(_)
+        ));
+        assert!(matches!(
+            DialOutcome::from(HandshakeError::SignatureError("bad".to_string())),
+            DialOutcome::ProtocolError(_)
+        ));
+        assert!(matches!(
+            DialOutcome::from(HandshakeError::UnexpectedIdentity("00".to_string())),
+            DialOutcome::ProtocolError(_)
+        ));
+    }
+}
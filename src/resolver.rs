@@ -0,0 +1,168 @@
+//! Resolution of the recipients' host names
+//!
+//! An enode's address can be a DNS host name, which is resolved only when
+//! the recipient is dialed, so that a dial always uses fresh addresses.
+//!
+//! The system resolver is used by default. A static map of host names to
+//! IP addresses, which is read from a file in the `/etc/hosts` format,
+//! can be used instead, e.g., for tests, or for hosts that aren't in DNS.
+
+use std::collections::HashMap;
+use std::fs;
+use std::net::{IpAddr, SocketAddr};
+use std::path::Path;
+
+use tokio::net::lookup_host;
+
+use crate::errors::ResolveError;
+use crate::input::EnodeAddress;
+
+/// Resolves the recipients' host names to IP addresses
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum Resolver {
+    /// The operating system's resolver
+    #[default]
+    System,
+    /// A static map of lowercase host names to their IP addresses
+    Static(HashMap<String, Vec<IpAddr>>),
+}
+
+impl Resolver {
+    /// Reads a static map of host names from a file in the `/etc/hosts` format
+    ///
+    /// Every line holds an IP address, followed by one or more host names.
+    /// Everything after `#` is a comment. A host name can be on several lines,
+    /// in which case it has all of their IP addresses, in order.
+    ///
+    /// # Errors
+    /// - [`ResolveError::ReadError`], if the file can't be read
+    /// - [`ResolveError::ParseError`], if a line has an invalid IP address, or no host name
+    pub fn from_hosts_file(path: &Path) -> Result<Self, ResolveError> {
+        let contents = fs::read_to_string(path)
+            .map_err(|err| ResolveError::ReadError(path.to_path_buf(), err.to_string()))?;
+
+        let mut hosts: HashMap<String, Vec<IpAddr>> = HashMap::new();
+
+        for (number, line) in contents.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default();
+            let mut fields = line.split_whitespace();
+            let Some(ip) = fields.next() else {
+                continue;
+            };
+            let parse_error =
+                |message: String| ResolveError::ParseError(path.to_path_buf(), number + 1, message);
+
+            let ip: IpAddr = ip
+                .parse()
+                .map_err(|_| parse_error(format!("invalid IP address {}", ip)))?;
+
+            let mut names = fields.peekable();
+            if names.peek().is_none() {
+                return Err(parse_error(format!("no host name for {}", ip)));
+            }
+            for name in names {
+                hosts.entry(name.to_ascii_lowercase()).or_default().push(ip);
+            }
+        }
+
+        Ok(Self::Static(hosts))
+    }
+
+    /// Resolves a recipient's address to the socket addresses to try, in order
+    ///
+    /// An IP address resolves to itself, without a lookup.
+    ///
+    /// # Errors
+    /// - [`ResolveError::LookupError`], if the system resolver fails
+    /// - [`ResolveError::NotFound`], if the host name has no addresses
+    pub async fn resolve(&self, address: &EnodeAddress) -> Result<Vec<SocketAddr>, ResolveError> {
+        let (host_name, port) = match address {
+            EnodeAddress::Ip(address) => return Ok(vec![*address]),
+            EnodeAddress::Dns(host_name, port) => (host_name, *port),
+        };
+
+        let addresses: Vec<SocketAddr> = match self {
+            Self::System => lookup_host((host_name.as_str(), port))
+                .await
+                .map_err(|err| ResolveError::LookupError(host_name.clone(), err.to_string()))?
+                .collect(),
+            Self::Static(hosts) => hosts
+                .get(&host_name.to_ascii_lowercase())
+                .into_iter()
+                .flatten()
+                .map(|ip| SocketAddr::new(*ip, port))
+                .collect(),
+        };
+
+        if addresses.is_empty() {
+            return Err(ResolveError::NotFound(host_name.clone()));
+        }
+
+        Ok(addresses)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::constants::{TEST_FILE, TEST_FILE_NON_EXISTENT, TEST_HOSTNAME, TEST_HOSTS_FILE};
+
+    use super::*;
+
+    fn dns(host_name: &str) -> EnodeAddress {
+        EnodeAddress::Dns(host_name.to_string(), 30303)
+    }
+
+    #[tokio::test]
+    async fn test_resolve_static() {
+        let resolver = Resolver::from_hosts_file(Path::new(TEST_HOSTS_FILE)).unwrap();
+
+        assert_eq!(
+            vec![TEST_HOSTNAME.parse::<SocketAddr>().unwrap()],
+            resolver.resolve(&dns("node1.lab.internal")).await.unwrap()
+        );
+        assert_eq!(
+            vec![
+                "127.0.0.1:30303".parse::<SocketAddr>().unwrap(),
+                "[::1]:30303".parse().unwrap()
+            ],
+            resolver.resolve(&dns("node2.lab.internal")).await.unwrap()
+        );
+        assert_eq!(
+            Err(ResolveError::NotFound("node3.lab.internal".to_string())),
+            resolver.resolve(&dns("node3.lab.internal")).await
+        );
+    }
+
+    #[tokio::test]
+    async fn test_resolve_ip() {
+        let address: SocketAddr = TEST_HOSTNAME.parse().unwrap();
+
+        for resolver in [Resolver::System, Resolver::Static(HashMap::new())] {
+            assert_eq!(
+                vec![address],
+                resolver.resolve(&address.into()).await.unwrap()
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_resolve_system() {
+        let addresses = Resolver::System.resolve(&dns("localhost")).await.unwrap();
+
+        assert!(addresses.iter().all(|address| address.ip().is_loopback()));
+    }
+
+    #[test]
+    fn test_from_hosts_file_invalid() {
+        assert!(matches!(
+            Resolver::from_hosts_file(Path::new(TEST_FILE_NON_EXISTENT)),
+            Err(ResolveError::ReadError(_, _))
+        ));
+
+        // the enodes aren't in the hosts file format
+        assert!(matches!(
+            Resolver::from_hosts_file(Path::new(TEST_FILE)),
+            Err(ResolveError::ParseError(_, 1, _))
+        ));
+    }
+}
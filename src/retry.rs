@@ -0,0 +1,130 @@
+//! Retrying of failed dials with exponential backoff
+
+use std::time::Duration;
+
+use rand_core::{OsRng, RngCore};
+
+use crate::constants::{BACKOFF_BASE, BACKOFF_MAX, RETRIES};
+use crate::messages::DisconnectReason;
+use crate::report::DialOutcome;
+
+/// How often and when to dial a recipient node again after a failure
+///
+/// Only failures that [`DialOutcome::is_retryable`] are retried.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RetryPolicy {
+    /// The maximum number of retries, after the first attempt
+    pub retries: u32,
+    /// The backoff before the first retry, which doubles with every further retry
+    pub backoff_base: Duration,
+    /// The upper bound of the backoff
+    pub backoff_max: Duration,
+    /// Whether to randomize the backoff, between a half of it and all of it
+    pub jitter: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            retries: RETRIES,
+            backoff_base: Duration::from_millis(BACKOFF_BASE),
+            backoff_max: Duration::from_millis(BACKOFF_MAX),
+            jitter: false,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Decides whether to retry after a failed attempt
+    ///
+    /// `attempt` is the number of the failed attempt, starting from one.
+    ///
+    /// # Returns
+    /// The backoff to wait before the retry, or `None` if it shouldn't be retried
+    pub fn backoff(&self, attempt: u32, outcome: &DialOutcome) -> Option<Duration> {
+        if attempt > self.retries || !outcome.is_retryable() {
+            return None;
+        }
+
+        // a recipient that has too many peers is unlikely to have a free slot soon
+        let backoff = if *outcome == DialOutcome::Disconnected(DisconnectReason::TooManyPeers) {
+            self.backoff_max
+        } else {
+            let factor = 2u32.saturating_pow(attempt - 1);
+            self.backoff_base
+                .saturating_mul(factor)
+                .min(self.backoff_max)
+        };
+
+        if self.jitter {
+            let half = backoff / 2;
+            let spread = (backoff - half).as_millis() as u64 + 1;
+            Some(half + Duration::from_millis(OsRng.next_u64() % spread))
+        } else {
+            Some(backoff)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::report::DialPhase;
+
+    use super::*;
+
+    fn policy(retries: u32, jitter: bool) -> RetryPolicy {
+        RetryPolicy {
+            retries,
+            backoff_base: Duration::from_millis(100),
+            backoff_max: Duration::from_millis(1000),
+            jitter,
+        }
+    }
+
+    #[test]
+    fn test_backoff_exponential() {
+        let policy = policy(5, false);
+        let outcome = DialOutcome::Refused;
+
+        let backoffs: Vec<u64> = (1..=5)
+            .map(|attempt| policy.backoff(attempt, &outcome).unwrap().as_millis() as u64)
+            .collect();
+
+        assert_eq!(vec![100, 200, 400, 800, 1000], backoffs);
+        assert_eq!(None, policy.backoff(6, &outcome));
+    }
+
+    #[test]
+    fn test_backoff_jitter() {
+        let policy = policy(5, true);
+
+        for _ in 0..100 {
+            let backoff = policy
+                .backoff(3, &DialOutcome::Timeout(DialPhase::Connect))
+                .unwrap();
+            assert!(backoff >= Duration::from_millis(200));
+            assert!(backoff <= Duration::from_millis(400));
+        }
+    }
+
+    #[test]
+    fn test_backoff_by_error_class() {
+        let policy = policy(3, false);
+
+        assert_eq!(
+            Some(Duration::from_millis(1000)),
+            policy.backoff(
+                1,
+                &DialOutcome::Disconnected(DisconnectReason::TooManyPeers)
+            )
+        );
+        assert_eq!(
+            None,
+            policy.backoff(1, &DialOutcome::InvalidNodeKey("bad".to_string()))
+        );
+        assert_eq!(
+            None,
+            RetryPolicy::default().backoff(1, &DialOutcome::Refused)
+        );
+    }
+}
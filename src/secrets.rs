@@ -0,0 +1,236 @@
+//! Secrets that are derived during the handshake procedure
+//!
+//! They are used for encrypting and authenticating all further communication
+//! between the two peers, on the RLPx framing layer.
+//!
+//! The derivation is specified at:
+//!
+//! [Secrets](https://github.com/ethereum/devp2p/blob/master/rlpx.md#secrets)
+
+use std::fmt;
+
+use ethereum_types::H256;
+use k256::ecdh::diffie_hellman;
+use k256::{NonZeroScalar, PublicKey};
+use secrecy::Zeroize;
+use sha3::{Digest, Keccak256};
+
+use crate::constants::KECCAK256_RATE;
+
+/// The side of the handshake procedure that we are on
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Role {
+    Initiator,
+    Recipient,
+}
+
+/// Secrets derived from a successful exchange of `auth` and `auth-ack` messages
+///
+/// - `aes-secret`, the key for frame encryption
+/// - `mac-secret`, the key for frame authentication
+/// - `egress-mac`, the Keccak-256 MAC state for sent frames
+/// - `ingress-mac`, the Keccak-256 MAC state for received frames
+///
+/// All of the secrets, including the MAC states, are zeroized on drop.
+pub struct Secrets {
+    pub(crate) aes_secret: [u8; 32],
+    pub(crate) mac_secret: [u8; 32],
+    pub(crate) egress_mac: Keccak256,
+    pub(crate) ingress_mac: Keccak256,
+}
+
+impl Secrets {
+    /// Derives the secrets from the handshake's ephemeral keys, nonces and messages
+    ///
+    /// - `ephemeral-key = ecdh.agree(ephemeral-privkey, remote-ephemeral-pubk)`
+    /// - `shared-secret = keccak256(ephemeral-key || keccak256(nonce || initiator-nonce))`
+    /// - `aes-secret = keccak256(ephemeral-key || shared-secret)`
+    /// - `mac-secret = keccak256(ephemeral-key || aes-secret)`
+    ///
+    /// The MAC states are seeded with `mac-secret ^ nonce || message`.
+    /// For the initiator, `egress-mac` uses the recipient's nonce and the `auth`
+    /// message, and `ingress-mac` uses the initiator's nonce and the `auth-ack`
+    /// message. It's the other way around for the recipient.
+    ///
+    /// `auth` and `ack` are the whole messages, as sent over the wire,
+    /// including their EIP-8 size prefixes.
+    pub fn derive(
+        role: Role,
+        ephemeral_secret: &NonZeroScalar,
+        remote_ephemeral_public_key: &PublicKey,
+        initiator_nonce: &H256,
+        recipient_nonce: &H256,
+        auth: &[u8],
+        ack: &[u8],
+    ) -> Self {
+        let ephemeral_key =
+            diffie_hellman(ephemeral_secret, remote_ephemeral_public_key.as_affine());
+        let ephemeral_key = ephemeral_key.raw_secret_bytes();
+
+        let mut nonce_hash = keccak256(&[recipient_nonce.as_bytes(), initiator_nonce.as_bytes()]);
+        let mut shared_secret = keccak256(&[ephemeral_key, &nonce_hash]);
+        let aes_secret = keccak256(&[ephemeral_key, &shared_secret]);
+        let mac_secret = keccak256(&[ephemeral_key, &aes_secret]);
+
+        nonce_hash.zeroize();
+        shared_secret.zeroize();
+
+        let mut initiator_seed = H256::from(mac_secret) ^ *recipient_nonce;
+        let mut initiator_mac = Keccak256::new();
+        initiator_mac.update(initiator_seed.as_bytes());
+        initiator_mac.update(auth);
+
+        let mut recipient_seed = H256::from(mac_secret) ^ *initiator_nonce;
+        let mut recipient_mac = Keccak256::new();
+        recipient_mac.update(recipient_seed.as_bytes());
+        recipient_mac.update(ack);
+
+        initiator_seed.0.zeroize();
+        recipient_seed.0.zeroize();
+
+        let (egress_mac, ingress_mac) = match role {
+            Role::Initiator => (initiator_mac, recipient_mac),
+            Role::Recipient => (recipient_mac, initiator_mac),
+        };
+
+        Self {
+            aes_secret,
+            mac_secret,
+            egress_mac,
+            ingress_mac,
+        }
+    }
+
+    /// The key for frame encryption
+    pub fn aes_secret(&self) -> &[u8; 32] {
+        &self.aes_secret
+    }
+
+    /// The key for frame authentication
+    pub fn mac_secret(&self) -> &[u8; 32] {
+        &self.mac_secret
+    }
+
+    /// The MAC state for sent frames
+    pub fn egress_mac(&self) -> &Keccak256 {
+        &self.egress_mac
+    }
+
+    /// The MAC state for received frames
+    pub fn ingress_mac(&self) -> &Keccak256 {
+        &self.ingress_mac
+    }
+}
+
+impl Drop for Secrets {
+    fn drop(&mut self) {
+        self.aes_secret.zeroize();
+        self.mac_secret.zeroize();
+
+        // Keccak-256 zeroizes its state on drop, but not its buffer of input that isn't
+        // absorbed yet. Absorbing a whole block of zeros overwrites all of that buffer.
+        self.egress_mac.update([0u8; KECCAK256_RATE]);
+        self.ingress_mac.update([0u8; KECCAK256_RATE]);
+    }
+}
+
+impl fmt::Debug for Secrets {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Secrets([REDACTED])")
+    }
+}
+
+/// Keccak-256 hash of the concatenation of the given byte slices
+fn keccak256(data: &[&[u8]]) -> [u8; 32] {
+    let mut hasher = Keccak256::new();
+    for part in data {
+        hasher.update(part);
+    }
+
+    hasher.finalize().into()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use k256::SecretKey;
+    use rand_core::OsRng;
+
+    use super::*;
+
+    /// Test vectors from EIP-8
+    const INITIATOR_EPHEMERAL_KEY: &str =
+        "869d6ecf5211f1cc60418a13b9d870b22959d0c16f02bec714c960dd2298a32d";
+    const RECIPIENT_EPHEMERAL_KEY: &str =
+        "e238eb8e04fee6511ab04c6dd3c89ce097b11f25d584863ac2b6d5b35b1847e4";
+    const INITIATOR_NONCE: &str =
+        "7e968bba13b6c50e2c4cd7f241cc0d64d1ac25c7f5952df231ac6a2bda8ee5d6";
+    const RECIPIENT_NONCE: &str =
+        "559aead08264d5795d3909718cdd05abd49572e84fe55590eef31a88a08fdffd";
+    const AES_SECRET: &str = "80e8632c05fed6fc2a13b0f8d31a3cf645366239170ea067065aba8e28bac487";
+    const MAC_SECRET: &str = "2ea74ec5dae199227dff1af715362700e989d889d7a493cb0639691efb8e5f98";
+
+    fn secret_key(hex_key: &str) -> SecretKey {
+        SecretKey::from_slice(&hex::decode(hex_key).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_derive_eip8_vectors() {
+        let initiator_ephemeral = secret_key(INITIATOR_EPHEMERAL_KEY);
+        let recipient_ephemeral = secret_key(RECIPIENT_EPHEMERAL_KEY);
+        let initiator_nonce = H256::from_str(INITIATOR_NONCE).unwrap();
+        let recipient_nonce = H256::from_str(RECIPIENT_NONCE).unwrap();
+
+        let secrets = Secrets::derive(
+            Role::Initiator,
+            &initiator_ephemeral.to_nonzero_scalar(),
+            &recipient_ephemeral.public_key(),
+            &initiator_nonce,
+            &recipient_nonce,
+            b"auth",
+            b"ack",
+        );
+
+        assert_eq!(AES_SECRET, hex::encode(secrets.aes_secret));
+        assert_eq!(MAC_SECRET, hex::encode(secrets.mac_secret));
+    }
+
+    #[test]
+    fn test_derive_both_sides_match() {
+        let initiator_ephemeral = SecretKey::random(&mut OsRng);
+        let recipient_ephemeral = SecretKey::random(&mut OsRng);
+        let initiator_nonce = H256::random();
+        let recipient_nonce = H256::random();
+
+        let initiator = Secrets::derive(
+            Role::Initiator,
+            &initiator_ephemeral.to_nonzero_scalar(),
+            &recipient_ephemeral.public_key(),
+            &initiator_nonce,
+            &recipient_nonce,
+            b"auth",
+            b"ack",
+        );
+        let recipient = Secrets::derive(
+            Role::Recipient,
+            &recipient_ephemeral.to_nonzero_scalar(),
+            &initiator_ephemeral.public_key(),
+            &initiator_nonce,
+            &recipient_nonce,
+            b"auth",
+            b"ack",
+        );
+
+        assert_eq!(initiator.aes_secret, recipient.aes_secret);
+        assert_eq!(initiator.mac_secret, recipient.mac_secret);
+        assert_eq!(
+            initiator.egress_mac.clone().finalize(),
+            recipient.ingress_mac.clone().finalize()
+        );
+        assert_eq!(
+            initiator.ingress_mac.clone().finalize(),
+            recipient.egress_mac.clone().finalize()
+        );
+    }
+}
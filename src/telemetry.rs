@@ -3,9 +3,19 @@
 use tracing_subscriber::{filter::LevelFilter, EnvFilter};
 
 /// Initialize tracing with the INFO log level as default
-pub fn init_tracing() {
-    let env_filter = EnvFilter::builder()
-        .with_default_directive(LevelFilter::INFO.into())
-        .from_env_lossy();
-    tracing_subscriber::fmt().with_env_filter(env_filter).init();
+///
+/// The given log filter takes precedence over the `RUST_LOG` environment variable.
+///
+/// Logs go to the standard error, so that the standard output
+/// is free for machine-readable output.
+pub fn init_tracing(log_level: Option<&str>) {
+    let builder = EnvFilter::builder().with_default_directive(LevelFilter::INFO.into());
+    let env_filter = match log_level {
+        Some(log_level) => builder.parse_lossy(log_level),
+        None => builder.from_env_lossy(),
+    };
+    tracing_subscriber::fmt()
+        .with_env_filter(env_filter)
+        .with_writer(std::io::stderr)
+        .init();
 }
@@ -0,0 +1,22 @@
+//! Utilities that the unit tests share
+
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+/// Held while the process environment is changed or read, as the tests run in parallel
+static ENV_LOCK: Mutex<()> = Mutex::new(());
+
+/// Locks the environment, e.g., while parsing arguments that can come from it
+pub fn lock_env() -> MutexGuard<'static, ()> {
+    ENV_LOCK.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Runs `f` with an environment variable that is set only while it runs
+pub fn with_env<T>(name: &str, value: &str, f: impl FnOnce() -> T) -> T {
+    let _env = lock_env();
+
+    std::env::set_var(name, value);
+    let result = f();
+    std::env::remove_var(name);
+
+    result
+}